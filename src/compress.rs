@@ -0,0 +1,552 @@
+//! Compression of the inputs and outputs.
+//!
+//! The inputs are decompressed according to their first bytes, so they can
+//! be compressed with gzip, bzip2, xz or zstd, or not at all. The outputs are
+//! compressed with the codec given by the user, gzip by default.
+//!
+//! The outputs can be compressed on several threads, like `pigz` does: they
+//! are split into blocks compressed independently, and written in order.
+//! The result is a multi-member gzip file (or several zstd frames, _etc_),
+//! which the decompressors read like a single stream. The threads can be
+//! shared by several outputs written at once.
+
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::mem;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use bzip2::read::MultiBzDecoder;
+use bzip2::write::BzEncoder;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
+
+
+/// The magic bytes starting a gzip stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// The magic bytes starting a bzip2 stream.
+const BZIP2_MAGIC: &[u8] = b"BZh";
+/// The magic bytes starting a xz stream.
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+/// The magic bytes starting a zstd frame.
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The size of the blocks compressed in parallel.
+const BLOCK_SIZE: usize = 1 << 20;
+/// The number of blocks each thread can have to compress, or compressed but
+/// not written yet.
+const BLOCKS_PER_THREAD: usize = 2;
+
+
+/// A compression codec, with its level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    /// gzip, with a level from 0 to 9.
+    Gzip(u32),
+    /// zstd, with a level from 1 to 22.
+    Zstd(i32),
+    /// bzip2, with a level from 1 to 9.
+    Bzip2(u32),
+    /// xz, with a level from 0 to 9.
+    Xz(u32),
+    /// No compression at all.
+    None,
+}
+
+impl Codec {
+    /// Create the file at `path`, to be written compressed with the codec.
+    pub fn create(self, path: &Path) -> io::Result<Encoder> {
+        let f = File::create(path)?;
+        Ok(match self {
+            Codec::Gzip(level) => {
+                Encoder::Gzip(GzEncoder::new(f, flate2::Compression::new(level)))
+            },
+            Codec::Zstd(level) => Encoder::Zstd(zstd::Encoder::new(f, level)?),
+            Codec::Bzip2(level) => {
+                Encoder::Bzip2(BzEncoder::new(f, bzip2::Compression::new(level)))
+            },
+            Codec::Xz(level) => Encoder::Xz(XzEncoder::new(f, level)),
+            Codec::None => Encoder::None(f),
+        })
+    }
+
+    /// Compress the `data` as a whole stream.
+    fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Gzip(level) => {
+                let mut e = GzEncoder::new(vec![], flate2::Compression::new(level));
+                e.write_all(data)?;
+                e.finish()
+            },
+            Codec::Zstd(level) => zstd::bulk::compress(data, level),
+            Codec::Bzip2(level) => {
+                let mut e = BzEncoder::new(vec![], bzip2::Compression::new(level));
+                e.write_all(data)?;
+                e.finish()
+            },
+            Codec::Xz(level) => {
+                let mut e = XzEncoder::new(vec![], level);
+                e.write_all(data)?;
+                e.finish()
+            },
+            Codec::None => Ok(data.to_vec()),
+        }
+    }
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    /// Parse a codec, optionally followed by its level: `gzip:6`, `zstd`,
+    /// `none`, _etc_.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, level) = match s.split_once(':') {
+            Some((name, level)) => (name, Some(level)),
+            None => (s, None),
+        };
+        let parse_level = |default: u32, min: u32, max: u32| match level {
+            None => Ok(default),
+            Some(level) => level
+                .parse()
+                .ok()
+                .filter(|level| (min..=max).contains(level))
+                .ok_or_else(|| {
+                    format!("invalid level {} for {}, expected {} to {}", level, name, min, max)
+                }),
+        };
+        match name {
+            "gzip" => Ok(Codec::Gzip(parse_level(6, 0, 9)?)),
+            "zstd" => Ok(Codec::Zstd(parse_level(3, 1, 22)? as i32)),
+            "bzip2" => Ok(Codec::Bzip2(parse_level(9, 1, 9)?)),
+            "xz" => Ok(Codec::Xz(parse_level(6, 0, 9)?)),
+            "none" => match level {
+                None => Ok(Codec::None),
+                Some(_) => Err("none has no level".to_string()),
+            },
+            _ => Err(format!("unknown codec {}, expected gzip, zstd, bzip2, xz or none", name)),
+        }
+    }
+}
+
+
+/// How to compress the outputs: the codec, and the number of threads.
+#[derive(Clone, Debug)]
+pub struct Compression {
+    pub codec: Codec,
+    pub threads: usize,
+    /// The threads shared by the files, if they are.
+    pool: Option<Pool>,
+}
+
+impl Compression {
+    /// Compress with `codec`, on `threads` threads for each file.
+    pub fn new(codec: Codec, threads: usize) -> Compression {
+        Compression { codec, threads, pool: None }
+    }
+
+    /// Tell whether the files are compressed on several threads.
+    fn is_parallel(&self) -> bool {
+        self.threads > 1 && self.codec != Codec::None
+    }
+
+    /// Share the threads between all the files created from now on, instead
+    /// of starting new ones for each file.
+    pub fn shared(self) -> Compression {
+        if !self.is_parallel() {
+            return self;
+        }
+        Compression { pool: Some(Pool::new(self.codec, self.threads)), ..self }
+    }
+
+    /// Create the file at `path`, to be written compressed, in parallel if
+    /// there are several threads.
+    pub fn create(&self, path: &Path) -> io::Result<Encoder> {
+        if !self.is_parallel() {
+            return self.codec.create(path);
+        }
+        let pool = match &self.pool {
+            Some(pool) => pool.clone(),
+            None => Pool::new(self.codec, self.threads),
+        };
+        Ok(Encoder::Parallel(ParallelEncoder::new(File::create(path)?, pool)))
+    }
+}
+
+
+/// A file being written compressed, which must be finished once written.
+pub enum Encoder {
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+    Bzip2(BzEncoder<File>),
+    Xz(XzEncoder<File>),
+    Parallel(ParallelEncoder),
+    None(File),
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Gzip(e) => e.write(buf),
+            Encoder::Zstd(e) => e.write(buf),
+            Encoder::Bzip2(e) => e.write(buf),
+            Encoder::Xz(e) => e.write(buf),
+            Encoder::Parallel(e) => e.write(buf),
+            Encoder::None(f) => f.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Gzip(e) => e.flush(),
+            Encoder::Zstd(e) => e.flush(),
+            Encoder::Bzip2(e) => e.flush(),
+            Encoder::Xz(e) => e.flush(),
+            Encoder::Parallel(e) => e.flush(),
+            Encoder::None(f) => f.flush(),
+        }
+    }
+}
+
+/// A stream which must be finished once written, to write its end.
+pub trait Finish: Write {
+    /// Write the end of the stream, and flush it.
+    fn finish(self) -> io::Result<()>;
+}
+
+impl Finish for Encoder {
+    fn finish(self) -> io::Result<()> {
+        let mut f = match self {
+            Encoder::Gzip(e) => e.finish()?,
+            Encoder::Zstd(e) => e.finish()?,
+            Encoder::Bzip2(e) => e.finish()?,
+            Encoder::Xz(e) => e.finish()?,
+            Encoder::Parallel(e) => return e.finish(),
+            Encoder::None(f) => f,
+        };
+        f.flush()
+    }
+}
+
+impl<W: Finish> Finish for io::BufWriter<W> {
+    fn finish(self) -> io::Result<()> {
+        self.into_inner().map_err(|e| e.into_error())?.finish()
+    }
+}
+
+
+/// A block to compress, and where to send it back once compressed.
+type Job = (Vec<u8>, mpsc::Sender<io::Result<Vec<u8>>>);
+
+/// Threads compressing the blocks of one or several `ParallelEncoder`.
+///
+/// The threads stop once the pool and all the encoders using it are
+/// dropped.
+#[derive(Clone, Debug)]
+pub struct Pool {
+    /// Where to send the blocks to compress. The queue holds a few blocks
+    /// per thread, so the encoders wait for the threads when it's full.
+    jobs: mpsc::SyncSender<Job>,
+    threads: usize,
+}
+
+impl Pool {
+    /// Start `threads` threads compressing with `codec`.
+    fn new(codec: Codec, threads: usize) -> Pool {
+        let (jobs, receiver) = mpsc::sync_channel::<Job>(threads * BLOCKS_PER_THREAD);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads {
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || loop {
+                // The lock is released once a job is received, so the
+                // other threads can wait for the next one.
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok((block, tx)) => {
+                        // The encoder is gone if it failed to write.
+                        let _ = tx.send(codec.compress(&block));
+                    },
+                    Err(_) => break,
+                }
+            });
+        }
+        Pool { jobs, threads }
+    }
+}
+
+/// A file being written compressed by several threads.
+///
+/// The data is split into blocks of `BLOCK_SIZE` bytes, each one being
+/// compressed as a whole stream by one of the threads of a `Pool`. The
+/// compressed blocks are written in their order by the thread writing the
+/// data.
+pub struct ParallelEncoder {
+    out: File,
+    /// The block being filled.
+    block: Vec<u8>,
+    /// The number of blocks sent to the threads so far.
+    blocks: usize,
+    pool: Pool,
+    /// Where to receive the blocks being compressed, in their order.
+    pending: VecDeque<mpsc::Receiver<io::Result<Vec<u8>>>>,
+    /// The most blocks being compressed at once.
+    max_pending: usize,
+}
+
+impl ParallelEncoder {
+    /// Make an encoder writing to `out`, compressing on the threads of the
+    /// `pool`.
+    pub fn new(out: File, pool: Pool) -> ParallelEncoder {
+        ParallelEncoder {
+            out,
+            block: Vec::with_capacity(BLOCK_SIZE),
+            blocks: 0,
+            max_pending: pool.threads * BLOCKS_PER_THREAD,
+            pool,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Send the current block to be compressed, first writing the oldest
+    /// compressed block if too many are pending.
+    fn send_block(&mut self) -> io::Result<()> {
+        if self.pending.len() == self.max_pending {
+            self.write_block()?;
+        }
+        let block = mem::replace(&mut self.block, Vec::with_capacity(BLOCK_SIZE));
+        let (tx, rx) = mpsc::channel();
+        self.pool.jobs.send((block, tx))
+            .map_err(|_| io::Error::other("the compression threads have stopped"))?;
+        self.pending.push_back(rx);
+        self.blocks += 1;
+        Ok(())
+    }
+
+    /// Wait for the oldest pending block to be compressed, and write it.
+    fn write_block(&mut self) -> io::Result<()> {
+        if let Some(rx) = self.pending.pop_front() {
+            let data = rx.recv()
+                .map_err(|_| io::Error::other("a compression thread has stopped"))??;
+            self.out.write_all(&data)?;
+        }
+        Ok(())
+    }
+
+    /// Write all the data.
+    fn finish(mut self) -> io::Result<()> {
+        // An empty stream is still made of one compressed block.
+        if !self.block.is_empty() || self.blocks == 0 {
+            self.send_block()?;
+        }
+        while !self.pending.is_empty() {
+            self.write_block()?;
+        }
+        self.out.flush()
+    }
+}
+
+impl Write for ParallelEncoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(BLOCK_SIZE - self.block.len());
+        self.block.extend_from_slice(&buf[..n]);
+        if self.block.len() == BLOCK_SIZE {
+            self.send_block()?;
+        }
+        Ok(n)
+    }
+
+    /// Compress and write the current block, even if it isn't full.
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.block.is_empty() {
+            self.send_block()?;
+        }
+        while !self.pending.is_empty() {
+            self.write_block()?;
+        }
+        self.out.flush()
+    }
+}
+
+
+/// Open the file at `path` to be read, decompressed according to its
+/// first bytes.
+pub fn open(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let start = reader.fill_buf()?;
+    let reader: Box<dyn Read + Send> = if start.starts_with(GZIP_MAGIC) {
+        Box::new(MultiGzDecoder::new(reader))
+    } else if start.starts_with(BZIP2_MAGIC) {
+        Box::new(MultiBzDecoder::new(reader))
+    } else if start.starts_with(XZ_MAGIC) {
+        Box::new(XzDecoder::new_multi_decoder(reader))
+    } else if start.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::Decoder::with_buffer(reader)?)
+    } else {
+        Box::new(reader)
+    };
+    Ok(reader)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::utils::temp_path;
+
+    /// Write the `data` to `path` with the `compression`, and read it back.
+    fn round_trip(compression: Compression, path: &Path, data: &[u8]) -> Vec<u8> {
+        let mut out = compression.create(path).unwrap();
+        out.write_all(data).unwrap();
+        out.finish().unwrap();
+        let mut read = vec![];
+        open(path).unwrap().read_to_end(&mut read).unwrap();
+        fs::remove_file(path).unwrap();
+        read
+    }
+
+    #[test]
+    fn codec_levels() {
+        assert_eq!("gzip".parse(), Ok(Codec::Gzip(6)));
+        assert_eq!("gzip:0".parse(), Ok(Codec::Gzip(0)));
+        assert_eq!("zstd".parse(), Ok(Codec::Zstd(3)));
+        assert_eq!("zstd:22".parse(), Ok(Codec::Zstd(22)));
+        assert_eq!("bzip2".parse(), Ok(Codec::Bzip2(9)));
+        assert_eq!("bzip2:1".parse(), Ok(Codec::Bzip2(1)));
+        assert_eq!("xz".parse(), Ok(Codec::Xz(6)));
+        assert_eq!("xz:9".parse(), Ok(Codec::Xz(9)));
+        assert_eq!("none".parse(), Ok(Codec::None));
+    }
+
+    #[test]
+    fn codec_errors() {
+        for s in &["gzip:10", "gzip:-1", "gzip:", "zstd:0", "zstd:23", "bzip2:0", "xz:10", "xz:a"] {
+            assert!(s.parse::<Codec>().unwrap_err().starts_with("invalid level"), "{}", s);
+        }
+        assert_eq!("none:1".parse::<Codec>(), Err("none has no level".to_string()));
+        assert!("lz4".parse::<Codec>().unwrap_err().starts_with("unknown codec lz4"));
+        assert!("".parse::<Codec>().unwrap_err().starts_with("unknown codec"));
+    }
+
+    #[test]
+    fn open_detects_the_codec() {
+        let data = b"INSERT INTO `page` VALUES (1,0,'A','',0,0,0.1,'','',1,1,'wikitext',NULL);\n";
+        let codecs = [
+            ("gzip", Codec::Gzip(6), GZIP_MAGIC),
+            ("zstd", Codec::Zstd(3), ZSTD_MAGIC),
+            ("bzip2", Codec::Bzip2(9), BZIP2_MAGIC),
+            ("xz", Codec::Xz(6), XZ_MAGIC),
+        ];
+        for (name, codec, magic) in &codecs {
+            let path = temp_path(name);
+            let mut out = codec.create(&path).unwrap();
+            out.write_all(data).unwrap();
+            out.finish().unwrap();
+            assert!(fs::read(&path).unwrap().starts_with(magic), "{}", name);
+            let mut read = vec![];
+            open(&path).unwrap().read_to_end(&mut read).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(read, &data[..], "{}", name);
+        }
+    }
+
+    #[test]
+    fn open_plain() {
+        // Plain data, including some shorter than the longest magic number.
+        for (n, data) in [&b""[..], b"B", b"BZ", b"-- MySQL dump\n"].iter().enumerate() {
+            let path = temp_path(&format!("plain-{}", n));
+            let compression = Compression::new(Codec::None, 1);
+            assert_eq!(round_trip(compression, &path, data), *data);
+        }
+    }
+
+    #[test]
+    fn open_concatenated_streams() {
+        let path = temp_path("concatenated");
+        let mut data = Codec::Gzip(6).compress(b"first ").unwrap();
+        data.extend(Codec::Gzip(6).compress(b"second").unwrap());
+        fs::write(&path, data).unwrap();
+        let mut read = String::new();
+        open(&path).unwrap().read_to_string(&mut read).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, "first second");
+    }
+
+    /// Return `len` bytes of data, not too easy to compress.
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8 ^ (i >> 12) as u8).collect()
+    }
+
+    #[test]
+    fn parallel_round_trip() {
+        let sizes = [0, BLOCK_SIZE - 1, BLOCK_SIZE, BLOCK_SIZE + 1, 3 * BLOCK_SIZE + 17];
+        for codec in &[Codec::Gzip(1), Codec::Zstd(1), Codec::Bzip2(1), Codec::Xz(0)] {
+            for threads in &[2, 4] {
+                for size in &sizes {
+                    let path = temp_path(&format!("parallel-{:?}-{}-{}", codec, threads, size));
+                    let compression = Compression::new(*codec, *threads);
+                    let data = data(*size);
+                    let read = round_trip(compression, &path, &data);
+                    assert!(read == data, "{:?} on {} threads, {} bytes", codec, threads, size);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_members() {
+        // Each block is a member of its own, so a decoder stopping at the
+        // end of the first one reads one block.
+        for (size, first) in &[(0, 0), (BLOCK_SIZE, BLOCK_SIZE), (BLOCK_SIZE + 1, BLOCK_SIZE)] {
+            let path = temp_path(&format!("members-{}", size));
+            let mut out = Compression::new(Codec::Gzip(1), 2).create(&path).unwrap();
+            // Written in uneven pieces, across the block boundaries.
+            for piece in data(*size).chunks(4093) {
+                out.write_all(piece).unwrap();
+            }
+            out.finish().unwrap();
+            let mut read = vec![];
+            let mut first_member = flate2::read::GzDecoder::new(File::open(&path).unwrap());
+            first_member.read_to_end(&mut read).unwrap();
+            assert_eq!(read.len(), *first);
+            let mut read = vec![];
+            open(&path).unwrap().read_to_end(&mut read).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(read.len(), *size);
+        }
+    }
+
+
+    #[test]
+    fn shared_pool() {
+        // The files are written at once, on the same threads.
+        let compression = Compression::new(Codec::Zstd(1), 2).shared();
+        let paths: Vec<PathBuf> = (0..3).map(|n| temp_path(&format!("shared-{}", n))).collect();
+        let mut outs: Vec<Encoder> =
+            paths.iter().map(|path| compression.create(path).unwrap()).collect();
+        let data = data(2 * BLOCK_SIZE + 5);
+        for piece in data.chunks(65_537) {
+            for out in &mut outs {
+                out.write_all(piece).unwrap();
+            }
+        }
+        for out in outs {
+            out.finish().unwrap();
+        }
+        for path in &paths {
+            let mut read = vec![];
+            open(path).unwrap().read_to_end(&mut read).unwrap();
+            fs::remove_file(path).unwrap();
+            assert!(read == data);
+        }
+    }
+
+}
@@ -0,0 +1,117 @@
+//! Logic specific to dgraph.
+//!
+//! See the dgraph [schema documentation][0].
+//!
+//! [0]: https://dgraph.io/docs/query-language/schema/
+
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::rdf::{Statement, Term};
+
+
+/// The predicate holding the type of the nodes.
+pub const TYPE_PREDICATE: &str = "dgraph.type";
+
+/// The type of the page nodes.
+pub const PAGE_TYPE: &str = "Page";
+
+/// The predicate holding the external ID of the nodes.
+pub const XID_PREDICATE: &str = "xid";
+
+
+/// How the page nodes are identified.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ids {
+    /// The page ID is used as the dgraph UID.
+    Uid,
+    /// The page ID, with a prefix, is used as a blank node label.
+    Blank,
+    /// Like `Blank`, and the label is also stored with the `xid` predicate.
+    Xid,
+}
+
+impl FromStr for Ids {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uid" => Ok(Ids::Uid),
+            "blank" => Ok(Ids::Blank),
+            "xid" => Ok(Ids::Xid),
+            _ => Err(format!("unknown dgraph IDs {}, expected uid, blank or xid", s))
+        }
+    }
+}
+
+/// Tell whether `prefix` can start a blank node label, so that the page IDs
+/// can be appended to it.
+pub fn is_valid_prefix(prefix: &str) -> bool {
+    !prefix.starts_with(['.', '-'])
+        && prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+
+/// The dgraph schema of the predicates written, to be saved to a file.
+pub struct Schema {
+    path: PathBuf,
+    predicates: BTreeSet<String>,
+}
+
+impl Schema {
+    /// Make an empty schema, to be saved to `path`.
+    pub fn new(path: PathBuf) -> Schema {
+        Schema { path, predicates: BTreeSet::new() }
+    }
+
+    /// Add the `predicate` to the schema, if not already there.
+    pub fn add(&mut self, predicate: &str) {
+        if predicate != TYPE_PREDICATE && !self.predicates.contains(predicate) {
+            self.predicates.insert(predicate.to_string());
+        }
+    }
+
+    /// Add the predicates of the `statements` to the schema.
+    pub fn add_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            if let Term::Iri(predicate) = &statement.predicate {
+                self.add(predicate);
+            }
+        }
+    }
+
+    /// Write the schema to its file: the type of each predicate, then the
+    /// `Page` type holding all of them.
+    pub fn save(&self) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(&self.path)?);
+        for predicate in &self.predicates {
+            writeln!(out, "{}: {} .", predicate, predicate_type(predicate))?;
+        }
+        writeln!(out)?;
+        writeln!(out, "type {} {{", PAGE_TYPE)?;
+        for predicate in &self.predicates {
+            writeln!(out, "    {}", predicate)?;
+        }
+        writeln!(out, "}}")?;
+        out.flush()
+    }
+}
+
+
+/// Return the type (and the indexes) of the `predicate`.
+fn predicate_type(predicate: &str) -> &'static str {
+    match predicate {
+        "namespace" => "int @index(int)",
+        "title" => "string @index(exact, term)",
+        "redirect" => "bool @index(bool)",
+        "linksto" => "[uid] @reverse @count",
+        "linkedfrom" => "[uid] @count",
+        "outdegree" | "indegree" => "int @index(int)",
+        "xid" => "string @index(exact) @upsert",
+        _ => "default"
+    }
+}
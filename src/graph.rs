@@ -0,0 +1,328 @@
+//! Dense IDs of the pages, and export of the links as a graph in compressed
+//! sparse row (CSR) format.
+//!
+//! The pages are given dense IDs, from 0 to N-1, which are mapped back to
+//! the pages with a CSV file, see `write_mapping`.
+//!
+//! The graph is written as a binary file, in little-endian:
+//!
+//! - the magic bytes `WDCCSR01`;
+//! - the number of nodes N, as an unsigned 64 bits integer;
+//! - the number of edges M, as an unsigned 64 bits integer;
+//! - the N+1 offsets, as unsigned 64 bits integers: the targets of the
+//!   links from the node `i` are at the indices `offsets[i]..offsets[i+1]`
+//!   of the targets array;
+//! - the M targets, as unsigned 32 bits integers, sorted for each node.
+//!
+//! Every array is aligned on the size of its integers, so the file can be
+//! memory-mapped.
+
+
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use fnv::FnvHashMap;
+
+use crate::compress::Finish;
+use crate::links::Link;
+use crate::output::{Record, RecordWriter};
+use crate::pages::Page;
+use crate::utils::csv_quote;
+
+
+/// The magic bytes starting a CSR file, with the version of the format.
+const MAGIC: &[u8; 8] = b"WDCCSR01";
+
+
+/// The order of the pages given dense IDs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DenseOrder {
+    /// By page ID.
+    Page,
+    /// By title, then namespace.
+    Title,
+}
+
+impl DenseOrder {
+    /// Sort the `pages` in this order.
+    pub fn sort(self, pages: &mut [&Page]) {
+        match self {
+            DenseOrder::Page => pages.sort_unstable_by_key(|page| page.pageid),
+            DenseOrder::Title => pages.sort_unstable_by(|a, b| {
+                (&a.title, a.namespace, a.pageid).cmp(&(&b.title, b.namespace, b.pageid))
+            }),
+        }
+    }
+}
+
+impl FromStr for DenseOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "page" => Ok(DenseOrder::Page),
+            "title" => Ok(DenseOrder::Title),
+            _ => Err(format!("unknown order {}, expected page or title", s))
+        }
+    }
+}
+
+
+/// The dense IDs, from 0 to N-1, given to N pages.
+pub struct DenseIds {
+    ids: FnvHashMap<u64, u32>,
+}
+
+impl DenseIds {
+    /// Give the dense IDs to the pages `pageids`, in their order.
+    pub fn new(pageids: impl Iterator<Item = u64>) -> DenseIds {
+        let ids = pageids
+            .enumerate()
+            .map(|(id, pageid)| (pageid, id as u32))
+            .collect();
+        DenseIds { ids }
+    }
+
+    /// Return the dense ID of the page `pageid`, if it has one.
+    pub fn get(&self, pageid: u64) -> Option<u32> {
+        self.ids.get(&pageid).copied()
+    }
+
+    /// Return the number of pages.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Replace the page ID of the `page` by its dense ID.
+    ///
+    /// Panics if the page has no dense ID.
+    pub fn remap_page(&self, page: &mut Page) {
+        page.pageid = self.get(page.pageid).expect("page without dense ID") as u64;
+    }
+
+    /// Replace the page IDs of both ends of the `link` by their dense IDs.
+    ///
+    /// Panics if a page has no dense ID.
+    pub fn remap_link(&self, link: &mut Link) {
+        link.from_id = self.get(link.from_id).expect("page without dense ID") as u64;
+        link.to_id = self.get(link.to_id).expect("page without dense ID") as u64;
+    }
+}
+
+/// Write the mapping of the dense IDs given to the `pages`, in their order,
+/// to `out` as CSV, with a header line, and finish it.
+pub fn write_mapping(pages: &[&Page], out: impl Finish) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    writeln!(out, "dense_id,pageid,namespace,title")?;
+    for (id, page) in pages.iter().enumerate() {
+        writeln!(out, "{},{},{},{}", id, page.pageid, page.namespace, csv_quote(&page.title))?;
+    }
+    out.finish()
+}
+
+/// Read a mapping written by `write_mapping` from the `reader`, and return
+/// the page ID of each dense ID.
+pub fn read_mapping(reader: impl BufRead) -> io::Result<Vec<u64>> {
+    let mut pageids = vec![];
+    for (n, line) in reader.lines().enumerate().skip(1) {
+        let line = line?;
+        let mut fields = line.splitn(3, ',');
+        let ids = (fields.next(), fields.next());
+        match ids {
+            (Some(id), Some(pageid)) if id.parse() == Ok(pageids.len()) => {
+                pageids.push(pageid.parse().map_err(|_| invalid_mapping(n))?)
+            },
+            _ => return Err(invalid_mapping(n)),
+        }
+    }
+    Ok(pageids)
+}
+
+/// Make the error of the invalid line `n` (from 0) of a mapping.
+fn invalid_mapping(n: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid mapping on line {}", n + 1))
+}
+
+
+/// Collect the links, and write them as a CSR file once they're all known.
+///
+/// Warning: all the links are kept in memory.
+pub struct CsrWriter {
+    path: PathBuf,
+    nodes: DenseIds,
+    /// The source and target of each link, as dense IDs.
+    edges: Vec<(u32, u32)>,
+}
+
+impl CsrWriter {
+    /// Make a writer of the graph between the `nodes` to the file at `path`.
+    pub fn new(path: PathBuf, nodes: DenseIds) -> CsrWriter {
+        CsrWriter { path, nodes, edges: vec![] }
+    }
+
+    /// Return the dense ID of the page `pageid`.
+    fn node(&self, pageid: u64) -> io::Result<u32> {
+        self.nodes.get(pageid).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("the page {} isn't a node of the graph", pageid)
+        ))
+    }
+}
+
+impl RecordWriter for CsrWriter {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        match record {
+            Record::Link(link) => {
+                let edge = (self.node(link.from_id)?, self.node(link.to_id)?);
+                self.edges.push(edge);
+                Ok(())
+            },
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the graph can only hold the links"
+            )),
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        let mut edges = mem::take(&mut self.edges);
+        edges.sort_unstable();
+
+        let mut offsets = vec![0u64; self.nodes.len() + 1];
+        for (from, _) in &edges {
+            offsets[*from as usize + 1] += 1;
+        }
+        for i in 0..self.nodes.len() {
+            offsets[i + 1] += offsets[i];
+        }
+
+        let mut out = BufWriter::new(File::create(&self.path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&(self.nodes.len() as u64).to_le_bytes())?;
+        out.write_all(&(edges.len() as u64).to_le_bytes())?;
+        for offset in offsets {
+            out.write_all(&offset.to_le_bytes())?;
+        }
+        for (_, to) in edges {
+            out.write_all(&to.to_le_bytes())?;
+        }
+        out.flush()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::io::BufReader;
+
+    use crate::compress::{self, Codec, Compression};
+    use crate::utils::temp_path;
+
+    fn page(pageid: u64, namespace: u32, title: &str) -> Page {
+        Page { pageid, namespace, title: title.to_string(), is_redirect: false }
+    }
+
+    fn link(from_id: u64, to_id: u64) -> Record {
+        Record::Link(Link { from_id, from_namespace: 0, to_id, to_namespace: 0 })
+    }
+
+    #[test]
+    fn mapping_round_trip() {
+        let pages = [
+            page(42, 0, "Antoine Meillet"),
+            page(7, 14, "Algèbre, linéaire"),
+            page(1_000_000_000_000, 0, "Le \"Quote\""),
+        ];
+        let pages: Vec<&Page> = pages.iter().collect();
+        for codec in &[Codec::None, Codec::Gzip(6)] {
+            let path = temp_path(&format!("mapping-{:?}", codec));
+            let compression = Compression::new(*codec, 1);
+            write_mapping(&pages, compression.create(&path).unwrap()).unwrap();
+            let mapping = read_mapping(BufReader::new(compress::open(&path).unwrap())).unwrap();
+            if *codec == Codec::None {
+                let csv = fs::read_to_string(&path).unwrap();
+                assert_eq!(csv.lines().nth(2), Some("1,7,14,\"Algèbre, linéaire\""));
+            }
+            fs::remove_file(&path).unwrap();
+            assert_eq!(mapping, vec![42, 7, 1_000_000_000_000]);
+        }
+    }
+
+    #[test]
+    fn mapping_errors() {
+        let header = "dense_id,pageid,namespace,title\n";
+        assert_eq!(read_mapping(header.as_bytes()).unwrap(), Vec::<u64>::new());
+        let invalid = [
+            ("1,42,0,A\n", 2), ("0,42,0,A\n0,7,0,B\n", 3), ("0,x,0,A\n", 2), ("0\n", 2)
+        ];
+        for (lines, n) in &invalid {
+            let mapping = format!("{}{}", header, lines);
+            let error = read_mapping(mapping.as_bytes()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(error.to_string(), format!("invalid mapping on line {}", n));
+        }
+    }
+
+    #[test]
+    fn dense_order() {
+        let pages = [page(3, 0, "B"), page(1, 14, "A"), page(2, 0, "A")];
+        let mut sorted: Vec<&Page> = pages.iter().collect();
+        DenseOrder::Page.sort(&mut sorted);
+        assert_eq!(sorted.iter().map(|page| page.pageid).collect::<Vec<_>>(), vec![1, 2, 3]);
+        DenseOrder::Title.sort(&mut sorted);
+        assert_eq!(sorted.iter().map(|page| page.pageid).collect::<Vec<_>>(), vec![2, 1, 3]);
+    }
+
+    /// Read the unsigned integers of `size` bytes of the `data`.
+    fn integers(data: &[u8], size: usize) -> Vec<u64> {
+        data.chunks(size)
+            .map(|bytes| bytes.iter().rev().fold(0, |n, byte| n << 8 | *byte as u64))
+            .collect()
+    }
+
+    #[test]
+    fn csr_layout() {
+        // The nodes 10, 20, 30 and 40 get the dense IDs 0 to 3.
+        let path = temp_path("csr");
+        let nodes = DenseIds::new(vec![10, 20, 30, 40].into_iter());
+        let mut writer: Box<dyn RecordWriter> = Box::new(CsrWriter::new(path.clone(), nodes));
+        for (from, to) in &[(30, 10), (10, 40), (10, 20), (30, 30), (10, 20)] {
+            writer.write(&link(*from, *to)).unwrap();
+        }
+        writer.finish().unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(&data[..8], MAGIC);
+        assert_eq!(integers(&data[8..24], 8), vec![4, 5]);
+        assert_eq!(integers(&data[24..64], 8), vec![0, 3, 3, 5, 5]);
+        assert_eq!(integers(&data[64..], 4), vec![1, 1, 3, 0, 2]);
+        assert_eq!(data.len(), 64 + 5 * 4);
+    }
+
+    #[test]
+    fn csr_empty() {
+        let path = temp_path("csr-empty");
+        let writer = Box::new(CsrWriter::new(path.clone(), DenseIds::new(vec![].into_iter())));
+        writer.finish().unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(&data[..8], MAGIC);
+        assert_eq!(integers(&data[8..], 8), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn csr_errors() {
+        let nodes = DenseIds::new(vec![10].into_iter());
+        let mut writer = CsrWriter::new(temp_path("csr-errors"), nodes);
+        let error = writer.write(&link(10, 20)).unwrap_err();
+        assert_eq!(error.to_string(), "the page 20 isn't a node of the graph");
+        assert!(writer.write(&Record::Page(page(10, 0, "A"))).is_err());
+    }
+}
@@ -8,18 +8,24 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::io::{self, Write};
 
-use fnv::FnvHashSet;
+use fnv::{FnvHashMap, FnvHashSet};
+use serde::Serialize;
 
+use crate::compress::Finish;
 use crate::pages::Page;
-use crate::utils::clean_title;
+use crate::rdf::{RdfFormat, Statement};
+use crate::utils::{clean_title, NamespaceFilter};
 
 
 /// Represent a link between two Wikipedia page.
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Link {
     pub from_id: u64,
+    pub from_namespace: u32,
     pub to_id: u64,
+    pub to_namespace: u32,
 }
 
 impl fmt::Display for Link {
@@ -31,10 +37,16 @@ impl fmt::Display for Link {
 impl Link {
     /// Get the `values` extracted from a SQL dump of the `pagelinks` table and
     /// make a `Link` out of it. Find the destination page's ID using `pages`,
-    /// and check for both pages existence using `pageids`.
+    /// and check for the source page's existence using `namespaces`, which
+    /// gives the namespace of the pages by ID.
+    ///
+    /// The namespace of the source is the one of the loaded page, not the
+    /// one of the `pl_from_namespace` column, which can be out of date: the
+    /// link then always goes between two pages with the namespaces they
+    /// were loaded with.
     pub fn from_sql(
         pages: &HashMap<(String, u32), Page>,
-        pageids: &FnvHashSet<u64>,
+        namespaces: &FnvHashMap<u64, u32>,
         values: Vec<String>
     ) -> Result<Link, LinkError> {
         if values.len() != 4 {
@@ -44,16 +56,15 @@ impl Link {
         let from_id = values[0].parse::<u64>().or(
             Err(LinkError::SQL { values: format!("{:?}", values) })
         )?;
+        let sql_namespace = values[3].parse::<u32>().or(
+            Err(LinkError::SQL { values: format!("{:?}", values) })
+        )?;
         // We check for the existence of the "from" pageid.
-        if !pageids.contains(&from_id) {
-            let from_namespace = values[3].parse::<u32>().or(
-                Err(LinkError::SQL { values: format!("{:?}", values) })
-            )?;
-            return Err(LinkError::PageNotFound{
+        let from_namespace = *namespaces.get(&from_id)
+            .ok_or(LinkError::PageNotFound{
                 title: format!("from pageid: {}", from_id),
-                namespace: from_namespace
-            });
-        }
+                namespace: sql_namespace
+            })?;
 
         let to_namespace = values[1].parse::<u32>().or(
             Err(LinkError::SQL { values: format!("{:?}", values) })
@@ -67,17 +78,162 @@ impl Link {
                 namespace: to_namespace
             })?;
 
-        Ok( Link { from_id, to_id: page.pageid } )
+        Ok( Link { from_id, from_namespace, to_id: page.pageid, to_namespace } )
+    }
+
+    /// Convert a Link to a RDF triple, made according to `rdf`.
+    pub fn to_rdf(&self, rdf: &RdfFormat) -> Statement {
+        rdf.statement(rdf.node(self.from_id), rdf.predicate("linksto"), rdf.node(self.to_id))
+    }
+
+    /// Convert a Link to the RDF triple of the reverse link, made according
+    /// to `rdf`.
+    pub fn to_reverse_rdf(&self, rdf: &RdfFormat) -> Statement {
+        rdf.statement(rdf.node(self.to_id), rdf.predicate("linkedfrom"), rdf.node(self.from_id))
+    }
+}
+
+
+/// Decide which links are converted.
+pub struct LinkFilter {
+    from_namespaces: NamespaceFilter,
+    to_namespaces: NamespaceFilter,
+    self_loops: bool,
+    /// The pages the links must be between, if restricted.
+    seeds: Option<FnvHashSet<u64>>,
+    /// The pairs of pages already linked, when removing duplicates.
+    seen: Option<FnvHashSet<(u64, u64)>>,
+}
+
+impl LinkFilter {
+    /// Make a filter keeping the links whose source and target namespaces
+    /// are accepted by `from_namespaces` and `to_namespaces`. If `dedup` is
+    /// true, only the first link between two pages is kept. If `self_loops`
+    /// is false, the links from a page to itself are removed.
+    ///
+    /// Warning: when removing duplicates, all the kept pairs of pages are
+    /// stored in memory.
+    pub fn new(
+        from_namespaces: NamespaceFilter,
+        to_namespaces: NamespaceFilter,
+        dedup: bool,
+        self_loops: bool
+    ) -> LinkFilter {
+        LinkFilter {
+            from_namespaces,
+            to_namespaces,
+            self_loops,
+            seeds: None,
+            seen: if dedup { Some(FnvHashSet::default()) } else { None },
+        }
+    }
+
+    /// Keep only the links between the `seeds` page IDs.
+    pub fn with_seeds(mut self, seeds: FnvHashSet<u64>) -> LinkFilter {
+        self.seeds = Some(seeds);
+        self
+    }
+
+    /// Tell whether the `page` can be an end of the kept links: it's one of
+    /// the seeds, if any, and its namespace is accepted for the source or the
+    /// target.
+    pub fn accepts_page(&self, page: &Page) -> bool {
+        self.is_seed(page.pageid)
+            && (self.from_namespaces.accepts(page.namespace)
+                || self.to_namespaces.accepts(page.namespace))
+    }
+
+    /// Tell whether the page `pageid` is one of the seeds, if any.
+    fn is_seed(&self, pageid: u64) -> bool {
+        match &self.seeds {
+            Some(seeds) => seeds.contains(&pageid),
+            None => true
+        }
     }
 
-    /// Convert a Link to a RDF triple.
-    pub fn to_rdf(&self) -> String {
-        format!("<{}> <linksto> <{}> .", self.from_id, self.to_id)
+    /// Tell whether the `link` is kept.
+    pub fn accepts(&mut self, link: &Link) -> bool {
+        if !self.from_namespaces.accepts(link.from_namespace)
+            || !self.to_namespaces.accepts(link.to_namespace) {
+            return false;
+        }
+        if !self.is_seed(link.from_id) || !self.is_seed(link.to_id) {
+            return false;
+        }
+        if !self.self_loops && link.from_id == link.to_id {
+            return false;
+        }
+        match &mut self.seen {
+            Some(seen) => seen.insert((link.from_id, link.to_id)),
+            None => true
+        }
+    }
+}
+
+
+/// The number of links from and to a page.
+#[derive(Clone, Debug, Serialize)]
+pub struct Degree {
+    pub pageid: u64,
+    pub outdegree: u64,
+    pub indegree: u64,
+}
+
+impl Degree {
+    /// Convert a Degree to two RDF triples, made according to `rdf`.
+    pub fn to_rdf(&self, rdf: &RdfFormat) -> Vec<Statement> {
+        vec![
+            rdf.statement(
+                rdf.node(self.pageid), rdf.predicate("outdegree"), rdf.integer(self.outdegree)
+            ),
+            rdf.statement(
+                rdf.node(self.pageid), rdf.predicate("indegree"), rdf.integer(self.indegree)
+            ),
+        ]
+    }
+}
+
+
+/// Count the links from and to each page.
+#[derive(Default)]
+pub struct Degrees {
+    /// The out-degree and in-degree of each page ID.
+    counts: FnvHashMap<u64, (u64, u64)>,
+}
+
+impl Degrees {
+    /// Count the `link`.
+    pub fn add(&mut self, link: &Link) {
+        self.counts.entry(link.from_id).or_default().0 += 1;
+        self.counts.entry(link.to_id).or_default().1 += 1;
+    }
+
+    /// Return the degree of each page, ordered by page ID. The pages without
+    /// any link aren't included.
+    pub fn sorted(&self) -> Vec<Degree> {
+        let mut degrees: Vec<Degree> = self.counts.iter()
+            .map(|(pageid, (outdegree, indegree))| Degree {
+                pageid: *pageid, outdegree: *outdegree, indegree: *indegree
+            })
+            .collect();
+        degrees.sort_unstable_by_key(|degree| degree.pageid);
+        degrees
+    }
+
+    /// Write the degrees to `out` as CSV, with a header line, and finish it.
+    pub fn write_csv(&self, out: impl Finish) -> io::Result<()> {
+        let mut out = io::BufWriter::new(out);
+        writeln!(out, "pageid,outdegree,indegree")?;
+        for degree in self.sorted() {
+            writeln!(out, "{},{},{}", degree.pageid, degree.outdegree, degree.indegree)?;
+        }
+        out.finish()
     }
 }
 
 
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum LinkError {
     SQL{values: String},
     PageNotFound{title: String, namespace: u32},
@@ -95,7 +251,47 @@ impl fmt::Display for LinkError {
 }
 
 impl Error for LinkError {
-    fn cause(&self) -> Option<&'static(dyn Error)> {
+    fn cause(&self) -> Option<&'static dyn Error> {
         None
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn from_sql_loaded_namespace() {
+        let cat = Page { pageid: 14, namespace: 14, title: "Cat".to_string(), is_redirect: false };
+        let mut pages = HashMap::new();
+        pages.insert(("Cat".to_string(), 14), cat);
+        let namespaces: FnvHashMap<u64, u32> = vec![(3, 0), (14, 14)].into_iter().collect();
+
+        // The pl_from_namespace column says 14, but the page 3 was loaded in 0.
+        let link = Link::from_sql(&pages, &namespaces, values(&["3", "14", "Cat", "14"])).unwrap();
+        assert_eq!((link.from_id, link.from_namespace), (3, 0));
+        assert_eq!((link.to_id, link.to_namespace), (14, 14));
+
+        let mut filter = LinkFilter::new(
+            NamespaceFilter::new(&[14]), NamespaceFilter::new(&[14]), false, true
+        );
+        assert!(!filter.accepts(&link));
+    }
+
+    #[test]
+    fn from_sql_unknown_pages() {
+        let pages = HashMap::new();
+        let namespaces: FnvHashMap<u64, u32> = vec![(3, 0)].into_iter().collect();
+        let unknown_from = Link::from_sql(&pages, &namespaces, values(&["4", "0", "Cat", "0"]));
+        assert!(matches!(unknown_from, Err(LinkError::PageNotFound { .. })));
+        let unknown_to = Link::from_sql(&pages, &namespaces, values(&["3", "0", "Cat", "0"]));
+        assert!(matches!(unknown_to, Err(LinkError::PageNotFound { .. })));
+        let invalid = Link::from_sql(&pages, &namespaces, values(&["x", "0", "Cat", "0"]));
+        assert!(matches!(invalid, Err(LinkError::SQL { .. })));
+    }
+}
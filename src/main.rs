@@ -1,32 +1,40 @@
-#[macro_use]
-extern crate lazy_static;
-
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufReader, BufRead};
 use std::process::exit;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
 use std::thread::JoinHandle;
 use std::time::Instant;
 
 use indicatif::HumanDuration;
-use flate2::{GzBuilder, Compression};
-use flate2::read::GzDecoder;
-use fnv::FnvHashSet;
+use fnv::{FnvHashMap, FnvHashSet};
 use structopt::StructOpt;
 
-use crate::pages::{pages_from_rdf, Page, PageError};
-use crate::links::{Link, LinkError};
+use crate::pages::{pages_from_rdf, DuplicatePolicy, Page, PageError};
+use crate::links::{Degrees, Link, LinkError, LinkFilter};
+use crate::compress::{Codec, Compression};
+use crate::dgraph::{Ids, Schema};
+use crate::graph::{CsrWriter, DenseIds, DenseOrder};
+use crate::output::{Content, Format, Options, Record, RecordWriter, Sharding};
+use crate::rdf::{Flavor, RdfFormat};
+use crate::utils::NamespaceFilter;
 
 mod sql;
+mod rdf;
+mod dgraph;
 mod utils;
+mod compress;
 mod pages;
 mod links;
+mod graph;
+mod output;
 
 
-/// Extract information from the Wikipedia dumps and generate RDF files
+/// Extract information from the Wikipedia dumps and generate RDF files (or
+/// files in other formats)
 #[derive(StructOpt)]
 struct  Cli {
     /// Silently ignore parsing errors
@@ -37,6 +45,277 @@ struct  Cli {
     cmd: Cmd
 }
 
+/// How to write (and read back) the RDF.
+#[derive(StructOpt)]
+struct RdfOpts {
+    /// The flavor of the RDF: dgraph (relative IRIs and plain literals) or
+    /// strict (absolute IRIs and typed literals).
+    #[structopt(long, default_value = "dgraph")]
+    rdf_flavor: Flavor,
+
+    /// The IRI prefixing the page IDs in strict flavor, for instance
+    /// `https://fr.wikipedia.org/wiki/?curid=`.
+    #[structopt(long)]
+    base_iri: Option<String>,
+
+    /// The IRI prefixing the predicates in strict flavor.
+    #[structopt(long)]
+    vocab_iri: Option<String>,
+
+    /// The language tag of the titles in strict flavor, for instance `fr`.
+    #[structopt(long)]
+    lang: Option<String>,
+
+    /// Write N-Quads instead of N-Triples, in the graph given by `--graph`.
+    #[structopt(long, requires = "graph")]
+    nquads: bool,
+
+    /// The IRI of the graph of the N-Quads, for instance `frwiki-20200901`.
+    #[structopt(long, requires = "nquads")]
+    graph: Option<String>,
+
+    /// How the pages are identified in dgraph flavor: uid (`<123>`), blank
+    /// (`_:p123`) or xid (blank, plus the `xid` predicate).
+    #[structopt(long, default_value = "uid")]
+    dgraph_ids: Ids,
+
+    /// The prefix of the blank node labels, for instance `frwiki.`.
+    #[structopt(long, default_value = "p")]
+    id_prefix: String,
+}
+
+impl RdfOpts {
+    /// Make the format of the RDF terms.
+    fn rdf_format(&self) -> Result<RdfFormat, Box<dyn Error>> {
+        let graph = if self.nquads { self.graph.clone() } else { None };
+        Ok(RdfFormat::new(
+            self.rdf_flavor, self.base_iri.clone(), self.vocab_iri.clone(), self.lang.clone(),
+            graph
+        )?.with_ids(self.dgraph_ids, &self.id_prefix)?)
+    }
+}
+
+/// How to write the output.
+#[derive(StructOpt)]
+struct OutputOpts {
+    /// The output format: ntriples, turtle, neo4j, json, jsonl, parquet,
+    /// sqlite, postgres, graphml, gexf, dot or cypher.
+    #[structopt(short, long, default_value = "ntriples")]
+    format: Format,
+
+    #[structopt(flatten)]
+    rdf: RdfOpts,
+
+    /// The path to write the dgraph schema of the predicates to. The type
+    /// of the nodes is then written too, with the `dgraph.type` predicate.
+    #[structopt(long)]
+    schema: Option<PathBuf>,
+
+    /// The number of objects per file in json format (100000 by default),
+    /// or of rows per statement in cypher format (1000 by default). In json
+    /// format, the number of each file is added to its name, for instance
+    /// `pages-00001.json.gz`.
+    #[structopt(long)]
+    batch_size: Option<usize>,
+
+    /// The path to write the script creating the table to, in postgres
+    /// format. The script adding its keys and indexes, to run once the rows
+    /// are copied, is written along, for instance to `pages-indexes.sql`.
+    #[structopt(long)]
+    ddl: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    compress: CompressOpts,
+
+    /// Split the output into this number of files, by hash of the page the
+    /// records are about. The number of each file is added to its name, for
+    /// instance `links-00001.rdf.gz`, and their list is written to
+    /// `links-manifest.txt`. The compression threads are shared between
+    /// the files.
+    #[structopt(long, conflicts_with = "shard-size")]
+    shards: Option<usize>,
+
+    /// Split the output into files of about this size, once compressed, for
+    /// instance `500MB` or `1GiB`. The files are named like with `--shards`.
+    #[structopt(long, parse(try_from_str = output::parse_size))]
+    shard_size: Option<u64>,
+}
+
+impl OutputOpts {
+    /// Check the dense IDs can be written as told by the options: dgraph
+    /// doesn't accept 0 as a uid, which is the first dense ID.
+    fn check_dense_ids(&self) -> Result<(), Box<dyn Error>> {
+        let uids = self.rdf.rdf_flavor == Flavor::Dgraph && self.rdf.dgraph_ids == Ids::Uid;
+        if uids && self.format.writes_uids() {
+            return Err(
+                "the dense ID 0 isn't a valid dgraph uid, use --dgraph-ids blank or xid".into()
+            );
+        }
+        Ok(())
+    }
+
+    /// Make the options of the writers. The reverse of the links are written
+    /// if `reverse` is true.
+    fn options(&self, reverse: bool) -> Result<Options, Box<dyn Error>> {
+        let mut rdf = self.rdf.rdf_format()?;
+        let schema = match &self.schema {
+            Some(path) => {
+                if rdf.flavor() != Flavor::Dgraph {
+                    return Err("the dgraph schema needs the dgraph flavor".into());
+                }
+                rdf = rdf.with_types();
+                Some(Schema::new(path.clone()))
+            },
+            None => None
+        };
+        Ok(Options {
+            rdf, reverse, schema, batch_size: self.batch_size, ddl: self.ddl.clone(),
+            compress: self.compress.compression()?, sharding: self.sharding()?
+        })
+    }
+
+    /// Make how to split the output into shards, if it is.
+    fn sharding(&self) -> Result<Option<Sharding>, Box<dyn Error>> {
+        match (self.shards, self.shard_size) {
+            (Some(0), _) => Err("there must be at least one shard".into()),
+            (_, Some(0)) => Err("the shard size must be at least one byte".into()),
+            (Some(n), _) => Ok(Some(Sharding::Count(n))),
+            (_, Some(size)) => Ok(Some(Sharding::Size(size))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// How to compress the output files.
+#[derive(StructOpt)]
+struct CompressOpts {
+    /// The compression of the output files: gzip, zstd, bzip2, xz or none,
+    /// optionally followed by the level, for instance `zstd:3`. The dense
+    /// IDs mapping and the degrees CSV file are compressed too.
+    #[structopt(long, default_value = "gzip")]
+    compress: Codec,
+
+    /// The number of threads compressing the output files. With several
+    /// threads, the files are compressed by blocks, and written as several
+    /// gzip members or zstd frames, which are read like a single stream.
+    #[structopt(long, default_value = "1")]
+    compress_threads: usize,
+}
+
+impl CompressOpts {
+    /// Make the compression of the output files.
+    fn compression(&self) -> Result<Compression, Box<dyn Error>> {
+        if self.compress_threads == 0 {
+            return Err("there must be at least one compression thread".into());
+        }
+        Ok(Compression::new(self.compress, self.compress_threads))
+    }
+}
+
+/// Where to read the links from, and which ones to keep.
+#[derive(StructOpt)]
+struct LinksInput {
+    /// The path to the pagelinks table dump.
+    pagelinks: PathBuf,
+
+    /// The path to the pages RDF triples.
+    pages: PathBuf,
+
+    /// What to do when several pages share the same title and
+    /// namespace: keep-first, keep-lowest-id, prefer-non-redirect
+    /// or fail.
+    #[structopt(long, default_value = "keep-first")]
+    on_duplicate: DuplicatePolicy,
+
+    /// Keep only the links between pages in these namespaces
+    /// (comma-separated).
+    #[structopt(short, long, require_delimiter = true)]
+    namespaces: Vec<u32>,
+
+    /// Keep only the links from pages in these namespaces
+    /// (comma-separated).
+    #[structopt(long, require_delimiter = true)]
+    from_namespaces: Vec<u32>,
+
+    /// Keep only the links to pages in these namespaces
+    /// (comma-separated).
+    #[structopt(long, require_delimiter = true)]
+    to_namespaces: Vec<u32>,
+
+    /// Convert only once the links between the same two pages.
+    ///
+    /// Warning: all the converted links are kept in memory.
+    #[structopt(long)]
+    dedup: bool,
+
+    /// Remove the links from a page to itself.
+    #[structopt(long)]
+    no_self_loops: bool,
+
+    /// Keep only the links between these pages (comma-separated page IDs).
+    #[structopt(long, require_delimiter = true)]
+    seeds: Vec<u64>,
+
+    /// Keep only the links between the pages listed in this file, one page
+    /// ID per line.
+    #[structopt(long)]
+    seeds_file: Option<PathBuf>,
+}
+
+impl LinksInput {
+    /// Tell whether the links are restricted to some pages.
+    fn has_seeds(&self) -> bool {
+        !self.seeds.is_empty() || self.seeds_file.is_some()
+    }
+
+    /// Make the filter of the links to keep.
+    fn filter(&self) -> Result<LinkFilter, Box<dyn Error>> {
+        // A link is kept if both its ends are accepted by `namespaces`
+        // and by their own filter.
+        let namespaces = NamespaceFilter::new(&self.namespaces);
+        let filter = LinkFilter::new(
+            namespaces.and(&NamespaceFilter::new(&self.from_namespaces)),
+            namespaces.and(&NamespaceFilter::new(&self.to_namespaces)),
+            self.dedup,
+            !self.no_self_loops
+        );
+        if !self.has_seeds() {
+            return Ok(filter);
+        }
+
+        let mut seeds: FnvHashSet<u64> = self.seeds.iter().copied().collect();
+        if let Some(path) = &self.seeds_file {
+            for (n, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+                let line = line?;
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let pageid = line.parse()
+                    .map_err(|_| format!("invalid page ID on line {} of the seeds: {}", n + 1, line))?;
+                seeds.insert(pageid);
+            }
+        }
+        Ok(filter.with_seeds(seeds))
+    }
+}
+
+/// How to give dense IDs to the pages.
+#[derive(StructOpt)]
+struct DenseOpts {
+    /// Give the pages dense IDs, from 0 to N-1, and write them instead of
+    /// the page IDs. The pages are numbered by page ID (page) or by title
+    /// (title).
+    #[structopt(long, requires = "dense-mapping")]
+    dense_ids: Option<DenseOrder>,
+
+    /// The path to write the mapping of the dense IDs to the pages to, as
+    /// CSV. For the links, without `--dense-ids`, the path to read the
+    /// mapping written along the pages from.
+    #[structopt(long)]
+    dense_mapping: Option<PathBuf>,
+}
+
 #[derive(StructOpt)]
 enum Cmd {
     /// Extract pages information from the Wikipedia SQL dump of the `pages`
@@ -44,12 +323,22 @@ enum Cmd {
     Pages {
         /// The path to the pages table dump.
         infile: PathBuf,
-        /// The path to write the RDF pages to.
+        /// The path to write the pages to.
         outfile: PathBuf,
 
         /// Keep only encyclopedia pages (i.e. namespace is 0).
         #[structopt(short, long)]
         encyclopedia: bool,
+
+        /// Keep only the pages in these namespaces (comma-separated).
+        #[structopt(short, long, require_delimiter = true, conflicts_with = "encyclopedia")]
+        namespaces: Vec<u32>,
+
+        #[structopt(flatten)]
+        dense: DenseOpts,
+
+        #[structopt(flatten)]
+        output: OutputOpts,
     },
 
     /// Extract the links information from the Wikipedia SQL dump of the
@@ -62,60 +351,122 @@ enum Cmd {
     /// Warning: the pages information are loaded into memory, which can
     /// be several GB.
     Links {
-        /// The path to the pagelinks table dump.
-        pagelinks: PathBuf,
+        #[structopt(flatten)]
+        input: LinksInput,
+
+        /// The path to write the links to.
+        outfile: PathBuf,
+
+        /// Also write the reverse links, with the `linkedfrom` predicate, in
+        /// ntriples, turtle or json format.
+        #[structopt(long)]
+        reverse: bool,
 
-        /// The path to the pages RDF triples.
-        pages: PathBuf,
+        /// Also write the out-degree and in-degree of each page, with the
+        /// `outdegree` and `indegree` predicates.
+        #[structopt(long)]
+        degrees: bool,
+
+        /// The path to write the out-degree and in-degree of each page to,
+        /// as CSV.
+        #[structopt(long)]
+        degrees_csv: Option<PathBuf>,
+
+        #[structopt(flatten)]
+        dense: DenseOpts,
+
+        #[structopt(flatten)]
+        output: OutputOpts,
+    },
+
+    /// Write the links as a graph in compressed sparse row format, from the
+    /// Wikipedia SQL dump of the `pagelinks` table.
+    ///
+    /// The pages are read like for the `links` command, and given dense
+    /// IDs in the order of their page ID. The graph is written as a binary
+    /// file, which isn't Gzipped, and the dense IDs are mapped back to the
+    /// pages with a CSV file.
+    ///
+    /// Warning: the pages and the links are loaded into memory.
+    Graph {
+        #[structopt(flatten)]
+        input: LinksInput,
 
-        /// The path to write the RDF links to.
+        /// The path to write the graph to.
         outfile: PathBuf,
+
+        /// The path to write the mapping of the dense IDs to the pages to,
+        /// as CSV.
+        mapping: PathBuf,
+
+        #[structopt(flatten)]
+        rdf: RdfOpts,
+
+        #[structopt(flatten)]
+        compress: CompressOpts,
     },
 }
 
 /// Extract the pages information in the SQL dump `infile` and write them
-/// as RDF triples to `outfile`.
-/// If `encyclopedia` is true, then convert only encyclopedia pages (*i.e.*
-/// namespace is 0).
-/// Both files are expected to be Gzipped.
-fn pages_to_rdf(
+/// to `outfile`, as told by `output`.
+/// Convert only the pages whose namespace is accepted by `namespaces`.
+/// The SQL dump is decompressed according to its first bytes.
+///
+/// If `dense` tells so, the pages are given dense IDs. They are then all
+/// loaded into memory, to be sorted.
+fn convert_pages(
     infile: PathBuf,
     outfile: PathBuf,
-    encyclopedia: bool,
+    namespaces: NamespaceFilter,
+    dense: DenseOpts,
+    output: OutputOpts,
     ignore_errors: bool
 ) -> Result<(), Box<dyn Error>> {
+    if dense.dense_ids.is_none() && dense.dense_mapping.is_some() {
+        return Err("the dense IDs mapping is only read by the links command".into());
+    }
+    if dense.dense_ids.is_some() {
+        output.check_dense_ids()?;
+    }
+
     // The channels, to pass read values between workers.
     // Note: because the lines are read way faster than they're parsed, they
     // end up taking all memory. Using sync_channel helps prevent this.
     let (lines_tx, lines_rx) = mpsc::sync_channel(3);
-    let (triples_tx, triples_rx) = mpsc::channel();
+    let (records_tx, records_rx) = mpsc::channel();
 
-    // Writing the RDF triples
-    let f = File::create(outfile)?;
-    let encoder = GzBuilder::new()
-        .write(f, Compression::default());
+    // Writing the records
+    let writer = output::create(output.format, Content::Pages, &outfile, output.options(false)?)?;
 
     let writing_worker = thread::spawn(move || {
-        utils::write_triples(encoder, triples_rx)
+        utils::write_records(writer, records_rx)
     });
 
     // Reading SQL dump
-    let f = File::open(infile)?;
-    let d = GzDecoder::new(f);
-    let reader = BufReader::new(d);
+    let reader = BufReader::new(compress::open(&infile)?);
 
-    let parsing_worker: JoinHandle<Result<(), PageError>> = thread::spawn(move || {
+    // With dense IDs, the pages are kept to be sorted instead of being sent.
+    let keep_pages = dense.dense_ids.is_some();
+    let pages_tx = records_tx.clone();
+    let parsing_worker: JoinHandle<Result<Vec<Page>, PageError>> = thread::spawn(move || {
+        let mut pages = vec![];
         while let Ok(line) = lines_rx.recv() {
             let parser = sql::InsertParser::from_line(line);
             for vals in parser {
                 let page = Page::from_sql(vals)?;
-                if encyclopedia && page.namespace != 0 {
+                if !namespaces.accepts(page.namespace) {
                     continue;
                 }
-                triples_tx.send(page.to_rdf()).unwrap();
+                if keep_pages {
+                    pages.push(page);
+                } else if pages_tx.send(Record::Page(page)).is_err() {
+                    // The writer stopped on an error, which is reported
+                    // when joining it.
+                    return Ok(pages);
+                }
             }
         }
-        Ok(())
+        Ok(pages)
     });
 
     for (n, line) in reader.lines().enumerate() {
@@ -145,61 +496,142 @@ fn pages_to_rdf(
 
     // Threads management
     drop(lines_tx);
-    parsing_worker.join().expect("Error while parsing SQL dump...")?;
-    writing_worker.join().expect("Error while writing RDF triples...")?;
+    let pages = parsing_worker.join().expect("Error while parsing SQL dump...")?;
+
+    if let (Some(order), Some(path)) = (dense.dense_ids, dense.dense_mapping) {
+        let mut sorted: Vec<&Page> = pages.iter().collect();
+        order.sort(&mut sorted);
+        graph::write_mapping(&sorted, output.compress.compression()?.create(&path)?)?;
+        for (id, page) in sorted.into_iter().enumerate() {
+            let page = Page { pageid: id as u64, ..page.clone() };
+            if records_tx.send(Record::Page(page)).is_err() {
+                break;
+            }
+        }
+    }
+    drop(records_tx);
+    writing_worker.join().expect("Error while writing records...")?;
 
     Ok(())
 }
 
 
+/// The options of the links conversion.
+struct LinksOptions {
+    /// What to do with the pages sharing the same title.
+    policy: DuplicatePolicy,
+    /// Which links to convert.
+    filter: LinkFilter,
+    /// Write the degree of each page as records.
+    degrees: bool,
+    /// Write the degree of each page as CSV to this file.
+    degrees_csv: Option<PathBuf>,
+    /// How to give dense IDs to the pages, if any.
+    dense: Option<DenseOpts>,
+    /// Write the pages which can be an end of the links before these.
+    pages: bool,
+    /// The compression of the degrees CSV file and of the mapping.
+    compress: Compression,
+}
+
+/// The pages, indexed by their title and namespace.
+type PagesIndex = HashMap<(String, u32), Page>;
+
 /// Extract the links information in the SQL dump `pagelinks` and write them
-/// as RDF triples to `outfile`. Use the pages information loaded from the
-/// RDF triples in `pages`.
+/// with the writer made by `make_writer` from the pages. Use the pages
+/// information loaded from the RDF triples in `pages`, which are read
+/// according to `rdf`. The pages sharing the same title, the links to
+/// convert and what to write along are chosen by `options`.
 ///
-/// The files are expected to be Gzipped.
+/// With dense IDs, these are either given to the loaded pages, or read from
+/// the mapping written along the pages, which are then expected to have them
+/// already.
+///
+/// The files are decompressed according to their first bytes.
 ///
 /// Warning: the pages are entirely loaded into memory, which can be huge.
-fn links_to_rdf(
+fn convert_links(
     pageslinks: PathBuf,
     pages: PathBuf,
-    outfile: PathBuf,
+    rdf: &RdfFormat,
+    options: LinksOptions,
+    make_writer: impl FnOnce(&PagesIndex) -> Result<Box<dyn RecordWriter>, Box<dyn Error>>,
     ignore_errors: bool
 ) -> Result<(), Box<dyn Error>> {
     // First, we load all pages
     println!("Loading pages...");
     let now = Instant::now();
-    let pages_f = File::open(pages)?;
-    let pages_d = GzDecoder::new(pages_f);
-    let pages = pages_from_rdf(BufReader::new(pages_d))?;
-    let pageids: FnvHashSet<u64> = pages.values().map(|page| page.pageid).collect();
+    let pages_d = compress::open(&pages)?;
+    let mut pages = pages_from_rdf(BufReader::new(pages_d), options.policy, rdf)?;
+    let ids = match options.dense {
+        Some(dense) => Some(dense_ids(&mut pages, dense, &options.compress)?),
+        None => None
+    };
+    let namespaces: FnvHashMap<u64, u32> = pages.values()
+        .map(|page| (page.pageid, page.namespace))
+        .collect();
     println!("Done! {} pages loaded in {}.", pages.len(), HumanDuration(now.elapsed()));
 
     // The channels, to pass read values between workers.
     // Note: because the lines are read way faster than they're parsed, they
     // end up taking all memory. Using sync_channel helps prevent this.
     let (lines_tx, lines_rx) = mpsc::sync_channel(3);
-    let (triples_tx, triples_rx) = mpsc::channel();
+    let (records_tx, records_rx) = mpsc::channel();
 
-    // Writing the RDF triples
-    let f = File::create(outfile)?;
-    let encoder = GzBuilder::new()
-        .write(f, Compression::default());
+    // Writing the records
+    let writer = make_writer(&pages)?;
 
     let writing_worker = thread::spawn(move || {
-        utils::write_triples(encoder, triples_rx)
+        utils::write_records(writer, records_rx)
     });
 
     // Reading the SQL dump
-    let f = File::open(pageslinks)?;
-    let d = GzDecoder::new(f);
-    let reader = BufReader::new(d);
+    let reader = BufReader::new(compress::open(&pageslinks)?);
+
+    let mut filter = options.filter;
+
+    // The graph formats need the pages, as nodes, before the links.
+    if options.pages {
+        let mut nodes: Vec<&Page> = pages.values()
+            .filter(|page| filter.accepts_page(page))
+            .collect();
+        nodes.sort_unstable_by_key(|page| page.pageid);
+        for page in nodes {
+            let mut page = page.clone();
+            if let Some(ids) = &ids {
+                ids.remap_page(&mut page);
+            }
+            if records_tx.send(Record::Page(page)).is_err() {
+                break;
+            }
+        }
+    }
 
-    let parsing_worker: JoinHandle<Result<(), LinkError>> = thread::spawn(move || {
+    let degrees_records = options.degrees;
+    let count_degrees = options.degrees || options.degrees_csv.is_some();
+    let parsing_worker: JoinHandle<Result<Degrees, LinkError>> = thread::spawn(move || {
+        let mut degrees = Degrees::default();
         while let Ok(line) = lines_rx.recv() {
             let parser = sql::InsertParser::from_line(line);
             for vals in parser {
-                match Link::from_sql(&pages, &pageids, vals) {
-                    Ok(link) => triples_tx.send(link.to_rdf()).unwrap(),
+                match Link::from_sql(&pages, &namespaces, vals) {
+                    Ok(link) => {
+                        if !filter.accepts(&link) {
+                            continue;
+                        }
+                        let mut link = link;
+                        if let Some(ids) = &ids {
+                            ids.remap_link(&mut link);
+                        }
+                        if count_degrees {
+                            degrees.add(&link);
+                        }
+                        // The writer stopped on an error, which is reported
+                        // when joining it.
+                        if records_tx.send(Record::Link(link)).is_err() {
+                            return Ok(degrees);
+                        }
+                    },
                     Err(e) => match e {
                         // We just want to ignore the links that don't
                         // come from/go to a known page.
@@ -210,7 +642,14 @@ fn links_to_rdf(
                 }
             }
         }
-        Ok(())
+        if degrees_records {
+            for degree in degrees.sorted() {
+                if records_tx.send(Record::Degree(degree)).is_err() {
+                    break;
+                }
+            }
+        }
+        Ok(degrees)
     });
 
     for (n, line) in reader.lines().enumerate() {
@@ -240,23 +679,118 @@ fn links_to_rdf(
 
     // Threads management
     drop(lines_tx);
-    parsing_worker.join().expect("Error while parsing SQL dump...")?;
-    writing_worker.join().expect("Error while writing RDF triples...")?;
+    let degrees = parsing_worker.join().expect("Error while parsing SQL dump...")?;
+    writing_worker.join().expect("Error while writing records...")?;
+
+    if let Some(path) = options.degrees_csv {
+        degrees.write_csv(options.compress.create(&path)?)?;
+    }
 
     Ok(())
 }
 
 
+/// Give the dense IDs to the `pages`, as told by `dense`, and return them.
+/// The mapping is written compressed with `compress`.
+///
+/// If the mapping is read, the pages are given their page IDs back, so the
+/// links can be read.
+fn dense_ids(
+    pages: &mut PagesIndex,
+    dense: DenseOpts,
+    compress: &Compression
+) -> Result<DenseIds, Box<dyn Error>> {
+    match (dense.dense_ids, dense.dense_mapping) {
+        (Some(order), Some(path)) => {
+            let mut sorted: Vec<&Page> = pages.values().collect();
+            order.sort(&mut sorted);
+            graph::write_mapping(&sorted, compress.create(&path)?)?;
+            Ok(DenseIds::new(sorted.iter().map(|page| page.pageid)))
+        },
+        (None, Some(path)) => {
+            let pageids = graph::read_mapping(BufReader::new(compress::open(&path)?))?;
+            for page in pages.values_mut() {
+                page.pageid = *pageids.get(page.pageid as usize)
+                    .ok_or_else(|| format!("the dense ID {} isn't in the mapping", page.pageid))?;
+            }
+            Ok(DenseIds::new(pageids.into_iter()))
+        },
+        _ => Err("the dense IDs need a mapping".into())
+    }
+}
+
+/// Make the writer of the graph to `outfile`, between the `pages` accepted by
+/// `namespaces`, and write the mapping of their dense IDs to `mapping`,
+/// compressed with `compress`.
+fn graph_writer(
+    pages: &PagesIndex,
+    outfile: PathBuf,
+    mapping: &Path,
+    namespaces: &NamespaceFilter,
+    compress: &Compression
+) -> Result<Box<dyn RecordWriter>, Box<dyn Error>> {
+    let mut nodes: Vec<&Page> = pages.values()
+        .filter(|page| namespaces.accepts(page.namespace))
+        .collect();
+    DenseOrder::Page.sort(&mut nodes);
+    graph::write_mapping(&nodes, compress.create(mapping)?)?;
+    Ok(Box::new(CsrWriter::new(outfile, DenseIds::new(nodes.iter().map(|page| page.pageid)))))
+}
+
+
 fn run(args: Cli) -> Result<(), Box<dyn Error>> {
     if args.ignore_errors {
         eprintln!("WARNING: ignoring parsing errors.");
     }
 
     match args.cmd {
-        Cmd::Pages{infile, outfile, encyclopedia} =>
-            pages_to_rdf(infile, outfile, encyclopedia, args.ignore_errors)?,
-        Cmd::Links {pagelinks, pages, outfile} =>
-            links_to_rdf(pagelinks, pages, outfile, args.ignore_errors)?,
+        Cmd::Pages{infile, outfile, encyclopedia, namespaces, dense, output} => {
+            let namespaces = if encyclopedia { vec![0] } else { namespaces };
+            convert_pages(
+                infile, outfile, NamespaceFilter::new(&namespaces), dense, output,
+                args.ignore_errors
+            )?
+        },
+        Cmd::Links { input, outfile, reverse, degrees, degrees_csv, dense, output } => {
+            if degrees && !output.format.holds_degrees() {
+                return Err("this output format can't hold the degrees, use --degrees-csv".into());
+            }
+            if output.format == Format::Dot && !input.has_seeds() {
+                return Err("the dot format needs the seeds of the subgraph".into());
+            }
+            if dense.dense_mapping.is_some() {
+                output.check_dense_ids()?;
+            }
+            // The options are checked before the pages are loaded, which
+            // takes a while.
+            let writer_options = output.options(reverse)?;
+            output::check(output.format, &writer_options)?;
+            let dense = if dense.dense_mapping.is_some() { Some(dense) } else { None };
+            let options = LinksOptions {
+                policy: input.on_duplicate, filter: input.filter()?, degrees, degrees_csv, dense,
+                pages: output.format.needs_pages(),
+                compress: output.compress.compression()?
+            };
+            convert_links(
+                input.pagelinks, input.pages, &output.rdf.rdf_format()?, options,
+                |_| output::create(output.format, Content::Links, &outfile, writer_options),
+                args.ignore_errors
+            )?
+        },
+        Cmd::Graph { input, outfile, mapping, rdf, compress } => {
+            let compress = compress.compression()?;
+            let options = LinksOptions {
+                policy: input.on_duplicate, filter: input.filter()?, degrees: false,
+                degrees_csv: None, dense: None, pages: false,
+                compress: compress.clone()
+            };
+            let namespaces = NamespaceFilter::new(&input.namespaces);
+            convert_links(
+                input.pagelinks, input.pages, &rdf.rdf_format()?, options,
+                |pages| graph_writer(pages, outfile, &mapping, &namespaces, &compress),
+                args.ignore_errors
+            )?
+        },
     }
 
     Ok(())
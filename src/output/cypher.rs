@@ -0,0 +1,253 @@
+//! Write the records as a Cypher script, for `cypher-shell`.
+//!
+//! The graph is the same as the one of the Neo4j import CSV files: the
+//! pages are nodes labelled `Page` plus the name of their namespace (and
+//! `Redirect` for the redirects), with the `pageid`, `title` and `namespace`
+//! properties, and the links are relationships typed `LINKS_TO`, not
+//! `linksto` like the RDF predicate, so that the script can add to a
+//! database imported from the CSV files. The degrees are set as the
+//! `outdegree` and `indegree` properties of the pages.
+//!
+//! The records are written in batches of `batch_size` rows, each one being
+//! a single `UNWIND` statement. The pages and the links are merged, so the
+//! script can be loaded into an existing database. The `Redirect` label and
+//! the labels of the named namespaces are removed from the merged pages
+//! before theirs are set, so that they don't keep the ones of an older
+//! dump.
+//!
+//! See the Cypher [manual][0].
+//!
+//! [0]: https://neo4j.com/docs/cypher-manual/current/clauses/unwind/
+
+
+use std::collections::BTreeMap;
+use std::io::{self, BufWriter, Write};
+
+use crate::compress::Finish;
+use crate::output::{Options, Record, RecordWriter};
+use crate::pages::{namespace_name, NAMESPACE_NAMES};
+
+
+/// The number of rows per statement, by default.
+const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// The statement making the page IDs unique, which also indexes them.
+const CONSTRAINT: &str =
+    "CREATE CONSTRAINT page_pageid IF NOT EXISTS FOR (p:Page) REQUIRE p.pageid IS UNIQUE;";
+
+
+pub struct CypherWriter<W: Write> {
+    out: BufWriter<W>,
+    batch_size: usize,
+    /// The rows of the pages not written yet, by namespace, as their label
+    /// can't be a parameter.
+    pages: BTreeMap<u32, Vec<String>>,
+    /// The rows of the links not written yet.
+    links: Vec<String>,
+    /// The rows of the degrees not written yet.
+    degrees: Vec<String>,
+}
+
+impl<W: Write> CypherWriter<W> {
+    /// Make a new writer, and write the constraint on the page IDs to `out`.
+    pub fn new(out: W, options: Options) -> io::Result<CypherWriter<W>> {
+        let mut out = BufWriter::new(out);
+        writeln!(out, "{}", CONSTRAINT)?;
+        Ok(CypherWriter {
+            out,
+            batch_size: options.batch_size.unwrap_or(DEFAULT_BATCH_SIZE),
+            pages: BTreeMap::new(),
+            links: vec![],
+            degrees: vec![],
+        })
+    }
+
+    /// Write the pages of the `namespace` not written yet.
+    fn write_pages(&mut self, namespace: u32) -> io::Result<()> {
+        let rows = self.pages.remove(&namespace).unwrap_or_default();
+        if rows.is_empty() {
+            return Ok(());
+        }
+        writeln!(self.out, "UNWIND [{}] AS row", rows.join(", "))?;
+        writeln!(self.out, "MERGE (p:Page {{pageid: row.pageid}})")?;
+        writeln!(self.out, "REMOVE {}", stale_labels(namespace))?;
+        writeln!(
+            self.out, "SET p:`{}`, p.title = row.title, p.namespace = {}",
+            namespace_name(namespace), namespace
+        )?;
+        writeln!(
+            self.out, "FOREACH (_ IN CASE WHEN row.redirect THEN [1] ELSE [] END | SET p:Redirect);"
+        )
+    }
+
+    /// Write the links not written yet.
+    fn write_links(&mut self) -> io::Result<()> {
+        if self.links.is_empty() {
+            return Ok(());
+        }
+        writeln!(self.out, "UNWIND [{}] AS row", self.links.join(", "))?;
+        writeln!(self.out, "MATCH (a:Page {{pageid: row[0]}}), (b:Page {{pageid: row[1]}})")?;
+        writeln!(self.out, "MERGE (a)-[:LINKS_TO]->(b);")?;
+        self.links.clear();
+        Ok(())
+    }
+
+    /// Write the degrees not written yet.
+    fn write_degrees(&mut self) -> io::Result<()> {
+        if self.degrees.is_empty() {
+            return Ok(());
+        }
+        writeln!(self.out, "UNWIND [{}] AS row", self.degrees.join(", "))?;
+        writeln!(self.out, "MATCH (p:Page {{pageid: row[0]}})")?;
+        writeln!(self.out, "SET p.outdegree = row[1], p.indegree = row[2];")?;
+        self.degrees.clear();
+        Ok(())
+    }
+}
+
+impl<W: Finish + Send> RecordWriter for CypherWriter<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        match record {
+            Record::Page(page) => {
+                let rows = self.pages.entry(page.namespace).or_default();
+                rows.push(format!(
+                    "{{pageid: {}, title: {}, redirect: {}}}",
+                    page.pageid, quote(&page.title), page.is_redirect
+                ));
+                if rows.len() == self.batch_size {
+                    self.write_pages(page.namespace)?;
+                }
+            },
+            Record::Link(link) => {
+                self.links.push(format!("[{}, {}]", link.from_id, link.to_id));
+                if self.links.len() == self.batch_size {
+                    self.write_links()?;
+                }
+            },
+            Record::Degree(degree) => {
+                self.degrees.push(
+                    format!("[{}, {}, {}]", degree.pageid, degree.outdegree, degree.indegree)
+                );
+                if self.degrees.len() == self.batch_size {
+                    self.write_degrees()?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        let namespaces: Vec<u32> = self.pages.keys().copied().collect();
+        for namespace in namespaces {
+            self.write_pages(namespace)?;
+        }
+        self.write_links()?;
+        self.write_degrees()?;
+        self.out.finish()
+    }
+}
+
+
+/// Return the labels a page of the `namespace` may have from an older dump,
+/// to be removed before its own are set: `Redirect`, and the labels of the
+/// other named namespaces.
+fn stale_labels(namespace: u32) -> String {
+    let mut labels = "p:Redirect".to_string();
+    for (_, name) in NAMESPACE_NAMES.iter().filter(|(n, _)| *n != namespace) {
+        labels.push_str(&format!(":`{}`", name));
+    }
+    labels
+}
+
+/// Quote the `value` as a Cypher string literal.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::links::Degree;
+    use crate::output::tests::{link, options, page, write};
+    use crate::output::{Content, Format};
+
+    #[test]
+    fn pages_by_namespace() {
+        let records = [page(1, 0, "A", false), page(2, 14, "C", true), page(3, 0, "B", false)];
+        let written = write(Format::Cypher, Content::Pages, "cypher-pages", &records, options());
+        let statement = |rows: &str, namespace: u32, label: &str| format!("\
+UNWIND [{}] AS row
+MERGE (p:Page {{pageid: row.pageid}})
+REMOVE {}
+SET p:`{}`, p.title = row.title, p.namespace = {}
+FOREACH (_ IN CASE WHEN row.redirect THEN [1] ELSE [] END | SET p:Redirect);
+", rows, stale_labels(namespace), label, namespace);
+        assert_eq!(written, format!(
+            "{}\n{}{}",
+            CONSTRAINT,
+            statement(
+                concat!(
+                    r#"{pageid: 1, title: "A", redirect: false}, "#,
+                    r#"{pageid: 3, title: "B", redirect: false}"#
+                ),
+                0, "Article"
+            ),
+            statement(r#"{pageid: 2, title: "C", redirect: true}"#, 14, "Category")
+        ));
+    }
+
+    #[test]
+    fn links_and_degrees_in_batches() {
+        let records = [
+            link(1, 2),
+            link(1, 3),
+            link(2, 1),
+            Record::Degree(Degree { pageid: 1, outdegree: 2, indegree: 1 }),
+        ];
+        let options = Options { batch_size: Some(2), ..options() };
+        let written = write(Format::Cypher, Content::Links, "cypher-links", &records, options);
+        assert_eq!(written, format!("{}\n{}", CONSTRAINT, "\
+UNWIND [[1, 2], [1, 3]] AS row
+MATCH (a:Page {pageid: row[0]}), (b:Page {pageid: row[1]})
+MERGE (a)-[:LINKS_TO]->(b);
+UNWIND [[2, 1]] AS row
+MATCH (a:Page {pageid: row[0]}), (b:Page {pageid: row[1]})
+MERGE (a)-[:LINKS_TO]->(b);
+UNWIND [[1, 2, 1]] AS row
+MATCH (p:Page {pageid: row[0]})
+SET p.outdegree = row[1], p.indegree = row[2];
+"));
+    }
+
+    #[test]
+    fn stale_labels_of_the_other_namespaces() {
+        let labels = stale_labels(14);
+        assert!(labels.starts_with("p:Redirect:`Article`:`Talk`:`User`:"));
+        assert!(labels.contains(":`Help`:`HelpTalk`:`CategoryTalk`:"));
+        assert!(labels.ends_with(":`Module`:`ModuleTalk`"));
+        assert!(!stale_labels(0).contains("`Article`"));
+    }
+
+    #[test]
+    fn quoted_strings() {
+        assert_eq!(quote("A"), r#""A""#);
+        assert_eq!(quote(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+        assert_eq!(quote("a\nb\rc\td"), r#""a\nb\rc\td""#);
+        assert_eq!(quote("l'été"), r#""l'été""#);
+    }
+}
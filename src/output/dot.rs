@@ -0,0 +1,59 @@
+//! Write the records as a Graphviz DOT graph.
+//!
+//! The pages are the nodes, identified by their page ID and labelled with
+//! their title (the redirects are dashed). The links are the directed edges
+//! between them. This is meant for small subgraphs, to be rendered with
+//! `dot` or `sfdp`.
+//!
+//! See the DOT [language][0].
+//!
+//! [0]: https://graphviz.org/doc/info/lang.html
+
+
+use std::io::{self, BufWriter, Write};
+
+use crate::compress::Finish;
+use crate::output::{Record, RecordWriter};
+
+
+pub struct DotWriter<W: Write> {
+    out: BufWriter<W>,
+}
+
+impl<W: Write> DotWriter<W> {
+    /// Make a new writer, and write the start of the graph to `out`.
+    pub fn new(out: W) -> io::Result<DotWriter<W>> {
+        let mut out = BufWriter::new(out);
+        writeln!(out, "digraph wikipedia {{")?;
+        writeln!(out, "    node [shape=box];")?;
+        Ok(DotWriter { out })
+    }
+}
+
+impl<W: Finish + Send> RecordWriter for DotWriter<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        match record {
+            Record::Page(page) => {
+                let style = if page.is_redirect { ", style=dashed" } else { "" };
+                writeln!(self.out, "    {} [label=\"{}\"{}];", page.pageid, escape(&page.title), style)
+            },
+            Record::Link(link) => writeln!(self.out, "    {} -> {};", link.from_id, link.to_id),
+            Record::Degree(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the dot format can't hold the degrees"
+            )),
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        writeln!(self.out, "}}")?;
+        self.out.finish()
+    }
+}
+
+
+/// Escape the `value` for a quoted string: the quotes, and the backslashes
+/// which would start escape sequences in the labels.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
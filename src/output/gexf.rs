@@ -0,0 +1,99 @@
+//! Write the records as a GEXF graph, for Gephi.
+//!
+//! The pages are the nodes, identified by their page ID and labelled with
+//! their title, with their namespace and redirect flag as attributes. The
+//! links are the directed edges between them. As GEXF lists all the nodes
+//! before the edges, the pages must be written before the links.
+//!
+//! See the GEXF [documentation][0].
+//!
+//! [0]: https://gexf.net/
+
+
+use std::io::{self, BufWriter, Write};
+
+use crate::compress::Finish;
+use crate::output::{Record, RecordWriter};
+use crate::utils::xml_escape;
+
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gexf xmlns="http://gexf.net/1.3" version="1.3">
+  <graph mode="static" defaultedgetype="directed">
+    <attributes class="node">
+      <attribute id="namespace" title="namespace" type="integer"/>
+      <attribute id="redirect" title="redirect" type="boolean">
+        <default>false</default>
+      </attribute>
+    </attributes>
+    <nodes>
+"#;
+
+const FOOTER: &str = "  </graph>
+</gexf>
+";
+
+
+pub struct GexfWriter<W: Write> {
+    out: BufWriter<W>,
+    /// The number of edges written, which gives their IDs. The nodes are
+    /// written until the first edge.
+    edges: u64,
+}
+
+impl<W: Write> GexfWriter<W> {
+    /// Make a new writer, and write the header of the graph to `out`.
+    pub fn new(out: W) -> io::Result<GexfWriter<W>> {
+        let mut out = BufWriter::new(out);
+        out.write_all(HEADER.as_bytes())?;
+        Ok(GexfWriter { out, edges: 0 })
+    }
+}
+
+impl<W: Finish + Send> RecordWriter for GexfWriter<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        match record {
+            Record::Page(_) if self.edges > 0 => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the gexf format needs the pages before the links"
+            )),
+            Record::Page(page) => {
+                write!(
+                    self.out,
+                    "      <node id=\"{}\" label=\"{}\"><attvalues>\
+                     <attvalue for=\"namespace\" value=\"{}\"/>",
+                    page.pageid, xml_escape(&page.title), page.namespace
+                )?;
+                if page.is_redirect {
+                    write!(self.out, "<attvalue for=\"redirect\" value=\"true\"/>")?;
+                }
+                writeln!(self.out, "</attvalues></node>")
+            },
+            Record::Link(link) => {
+                if self.edges == 0 {
+                    writeln!(self.out, "    </nodes>\n    <edges>")?;
+                }
+                writeln!(
+                    self.out, "      <edge id=\"{}\" source=\"{}\" target=\"{}\"/>",
+                    self.edges, link.from_id, link.to_id
+                )?;
+                self.edges += 1;
+                Ok(())
+            },
+            Record::Degree(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the gexf format can't hold the degrees"
+            )),
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        if self.edges == 0 {
+            writeln!(self.out, "    </nodes>")?;
+        } else {
+            writeln!(self.out, "    </edges>")?;
+        }
+        self.out.write_all(FOOTER.as_bytes())?;
+        self.out.finish()
+    }
+}
@@ -0,0 +1,78 @@
+//! Write the records as a GraphML graph.
+//!
+//! The pages are the nodes, identified by `n` and their page ID, with their
+//! title, namespace and redirect flag as data. The links are the directed
+//! edges between them.
+//!
+//! See the GraphML [specification][0].
+//!
+//! [0]: http://graphml.graphdrawing.org/specification.html
+
+
+use std::io::{self, BufWriter, Write};
+
+use crate::compress::Finish;
+use crate::output::{Record, RecordWriter};
+use crate::utils::xml_escape;
+
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns"
+         xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+         xsi:schemaLocation="http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd">
+  <key id="title" for="node" attr.name="title" attr.type="string"/>
+  <key id="namespace" for="node" attr.name="namespace" attr.type="int"/>
+  <key id="redirect" for="node" attr.name="redirect" attr.type="boolean">
+    <default>false</default>
+  </key>
+  <graph id="wikipedia" edgedefault="directed">
+"#;
+
+const FOOTER: &str = "  </graph>
+</graphml>
+";
+
+
+pub struct GraphMlWriter<W: Write> {
+    out: BufWriter<W>,
+}
+
+impl<W: Write> GraphMlWriter<W> {
+    /// Make a new writer, and write the header of the graph to `out`.
+    pub fn new(out: W) -> io::Result<GraphMlWriter<W>> {
+        let mut out = BufWriter::new(out);
+        out.write_all(HEADER.as_bytes())?;
+        Ok(GraphMlWriter { out })
+    }
+}
+
+impl<W: Finish + Send> RecordWriter for GraphMlWriter<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        match record {
+            Record::Page(page) => {
+                write!(
+                    self.out,
+                    "    <node id=\"n{}\"><data key=\"title\">{}</data>\
+                     <data key=\"namespace\">{}</data>",
+                    page.pageid, xml_escape(&page.title), page.namespace
+                )?;
+                if page.is_redirect {
+                    write!(self.out, "<data key=\"redirect\">true</data>")?;
+                }
+                writeln!(self.out, "</node>")
+            },
+            Record::Link(link) => writeln!(
+                self.out, "    <edge source=\"n{}\" target=\"n{}\"/>", link.from_id, link.to_id
+            ),
+            Record::Degree(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the graphml format can't hold the degrees"
+            )),
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.out.write_all(FOOTER.as_bytes())?;
+        self.out.finish()
+    }
+}
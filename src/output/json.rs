@@ -0,0 +1,294 @@
+//! Write the records as JSON mutations for `dgraph live`.
+//!
+//! Each page is an object holding its predicates, and the links from the
+//! same page are grouped in one object, under the `linksto` predicate. The
+//! objects are written in JSON arrays, in several files holding at most
+//! `batch_size` objects each: `pages.json.gz` becomes `pages-00001.json.gz`,
+//! `pages-00002.json.gz`, and so on.
+//!
+//! See the dgraph [mutation documentation][0].
+//!
+//! [0]: https://dgraph.io/docs/mutations/json-mutation-format/
+
+
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use serde_json::{json, Map, Value};
+
+use crate::compress::{Compression, Encoder, Finish};
+use crate::dgraph::{Ids, Schema, PAGE_TYPE, TYPE_PREDICATE, XID_PREDICATE};
+use crate::links::Link;
+use crate::output::{numbered_path, Options, Record, RecordWriter};
+use crate::rdf::RdfFormat;
+
+
+/// The number of objects per file, by default.
+const DEFAULT_BATCH_SIZE: usize = 100_000;
+
+pub struct JsonWriter {
+    /// The path the paths of the files are made from.
+    path: PathBuf,
+    rdf: RdfFormat,
+    reverse: bool,
+    schema: Option<Schema>,
+    batch_size: usize,
+    compress: Compression,
+    /// The file being written, if any.
+    out: Option<BufWriter<Encoder>>,
+    /// The number of files opened so far.
+    files: usize,
+    /// The number of objects written to the current file.
+    objects: usize,
+    /// The source and the targets of the links not written yet.
+    links: Option<(u64, Vec<u64>)>,
+}
+
+impl JsonWriter {
+    /// Make a new writer, whose files are named after `path`.
+    pub fn new(path: PathBuf, options: Options) -> JsonWriter {
+        JsonWriter {
+            path,
+            rdf: options.rdf,
+            reverse: options.reverse,
+            schema: options.schema,
+            batch_size: options.batch_size.unwrap_or(DEFAULT_BATCH_SIZE),
+            compress: options.compress,
+            out: None,
+            files: 0,
+            objects: 0,
+            links: None,
+        }
+    }
+
+    /// Return the uid of the page `pageid`: its hexadecimal form, or its
+    /// blank node label.
+    fn uid(&self, pageid: u64) -> String {
+        match self.rdf.ids() {
+            Ids::Uid => format!("{:#x}", pageid),
+            Ids::Blank | Ids::Xid => format!("_:{}", self.rdf.label(pageid)),
+        }
+    }
+
+    /// Make the object of the page `pageid`, holding only its uid.
+    fn node(&self, pageid: u64) -> Map<String, Value> {
+        let mut object = Map::new();
+        object.insert("uid".to_string(), Value::String(self.uid(pageid)));
+        object
+    }
+
+    /// Write the `object`, in a new file if the current one is full.
+    fn write_object(&mut self, object: Map<String, Value>) -> io::Result<()> {
+        if let Some(schema) = &mut self.schema {
+            for predicate in object.keys().filter(|key| *key != "uid") {
+                schema.add(predicate);
+            }
+        }
+        if self.objects == self.batch_size {
+            self.close()?;
+        }
+        let out = match &mut self.out {
+            Some(out) => {
+                out.write_all(b",\n")?;
+                out
+            },
+            None => {
+                self.files += 1;
+                self.objects = 0;
+                let out = self.out.insert(
+                    BufWriter::new(self.compress.create(&numbered_path(&self.path, self.files))?)
+                );
+                out.write_all(b"[\n")?;
+                out
+            },
+        };
+        serde_json::to_writer(&mut *out, &object)?;
+        self.objects += 1;
+        Ok(())
+    }
+
+    /// Close the JSON array of the current file, if any, and the file.
+    fn close(&mut self) -> io::Result<()> {
+        if let Some(mut out) = self.out.take() {
+            out.write_all(b"\n]\n")?;
+            out.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Write the links not written yet, as one object.
+    fn write_links(&mut self) -> io::Result<()> {
+        if let Some((from_id, to_ids)) = self.links.take() {
+            let mut object = self.node(from_id);
+            let targets = to_ids.iter().map(|to_id| Value::Object(self.node(*to_id))).collect();
+            object.insert("linksto".to_string(), Value::Array(targets));
+            self.write_object(object)?;
+        }
+        Ok(())
+    }
+
+    /// Add the `link` to the links not written yet, writing these first if
+    /// they come from another page.
+    fn add_link(&mut self, link: &Link) -> io::Result<()> {
+        match &mut self.links {
+            Some((from_id, to_ids)) if *from_id == link.from_id => to_ids.push(link.to_id),
+            _ => {
+                self.write_links()?;
+                self.links = Some((link.from_id, vec![link.to_id]));
+            }
+        }
+        if self.reverse {
+            let mut object = self.node(link.to_id);
+            object.insert("linkedfrom".to_string(), json!([self.node(link.from_id)]));
+            self.write_object(object)?;
+        }
+        Ok(())
+    }
+}
+
+impl RecordWriter for JsonWriter {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        if let Record::Link(link) = record {
+            return self.add_link(link);
+        }
+        self.write_links()?;
+
+        match record {
+            Record::Page(page) => {
+                let mut object = self.node(page.pageid);
+                object.insert("namespace".to_string(), json!(page.namespace));
+                object.insert("title".to_string(), json!(page.title));
+                if page.is_redirect {
+                    object.insert("redirect".to_string(), json!(true));
+                }
+                if self.rdf.types() {
+                    object.insert(TYPE_PREDICATE.to_string(), json!(PAGE_TYPE));
+                }
+                if self.rdf.ids() == Ids::Xid {
+                    object.insert(XID_PREDICATE.to_string(), json!(self.rdf.label(page.pageid)));
+                }
+                self.write_object(object)
+            },
+            Record::Degree(degree) => {
+                let mut object = self.node(degree.pageid);
+                object.insert("outdegree".to_string(), json!(degree.outdegree));
+                object.insert("indegree".to_string(), json!(degree.indegree));
+                self.write_object(object)
+            },
+            Record::Link(_) => unreachable!(),
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.write_links()?;
+        // Always write one file, even without any object.
+        if self.files == 0 {
+            self.files = 1;
+            let mut out = self.compress.create(&numbered_path(&self.path, 1))?;
+            out.write_all(b"[]\n")?;
+            out.finish()?;
+        }
+        self.close()?;
+        if let Some(schema) = &self.schema {
+            schema.save()?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::output::tests::{link, options, page};
+    use crate::utils::temp_path;
+
+    /// Write the `records` to files named after `name`, according to
+    /// `options`, and return the content of each file.
+    fn write(name: &str, records: &[Record], options: Options) -> Vec<String> {
+        let path = temp_path(&format!("{}.json", name));
+        let mut writer = Box::new(JsonWriter::new(path.clone(), options));
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        let mut files = vec![];
+        for n in 1.. {
+            let file = numbered_path(&path, n);
+            match fs::read_to_string(&file) {
+                Ok(content) => files.push(content),
+                Err(_) => break,
+            }
+            fs::remove_file(&file).unwrap();
+        }
+        files
+    }
+
+    #[test]
+    fn batches() {
+        let records = [page(1, 0, "A", false), page(2, 14, "C", true), link(1, 2)];
+        let options = Options { batch_size: Some(2), ..options() };
+        assert_eq!(write("json-batches", &records, options), vec![
+            r#"[
+{"namespace":0,"title":"A","uid":"0x1"},
+{"namespace":14,"redirect":true,"title":"C","uid":"0x2"}
+]
+"#,
+            r#"[
+{"linksto":[{"uid":"0x2"}],"uid":"0x1"}
+]
+"#,
+        ]);
+    }
+
+    #[test]
+    fn links_grouped_by_source() {
+        let records = [link(1, 2), link(1, 3), link(2, 1), link(1, 2)];
+        assert_eq!(write("json-links", &records, options()), vec![
+            r#"[
+{"linksto":[{"uid":"0x2"},{"uid":"0x3"}],"uid":"0x1"},
+{"linksto":[{"uid":"0x1"}],"uid":"0x2"},
+{"linksto":[{"uid":"0x2"}],"uid":"0x1"}
+]
+"#,
+        ]);
+    }
+
+    #[test]
+    fn reverse_links() {
+        let records = [link(1, 2), link(1, 3)];
+        let options = Options { reverse: true, batch_size: Some(2), ..options() };
+        assert_eq!(write("json-reverse", &records, options), vec![
+            r#"[
+{"linkedfrom":[{"uid":"0x1"}],"uid":"0x2"},
+{"linkedfrom":[{"uid":"0x1"}],"uid":"0x3"}
+]
+"#,
+            r#"[
+{"linksto":[{"uid":"0x2"},{"uid":"0x3"}],"uid":"0x1"}
+]
+"#,
+        ]);
+    }
+
+    #[test]
+    fn blank_nodes() {
+        let rdf = options().rdf.with_ids(Ids::Blank, "fr.").unwrap();
+        let records = [page(1, 0, "A", false), link(1, 2)];
+        assert_eq!(write("json-blank", &records, Options { rdf, ..options() }), vec![
+            r#"[
+{"namespace":0,"title":"A","uid":"_:fr.1"},
+{"linksto":[{"uid":"_:fr.2"}],"uid":"_:fr.1"}
+]
+"#,
+        ]);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(write("json-empty", &[], options()), vec!["[]\n"]);
+    }
+}
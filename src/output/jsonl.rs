@@ -0,0 +1,39 @@
+//! Write the records as JSON Lines, one object per line.
+//!
+//! Each object holds the fields of the record, plus its kind in the `type`
+//! field (`page`, `link` or `degree`). For instance:
+//!
+//!     {"type":"page","pageid":3,"namespace":0,"title":"Antoine Meillet","is_redirect":false}
+//!     {"type":"link","from_id":3,"from_namespace":0,"to_id":7,"to_namespace":0}
+//!
+//! See the JSON Lines [documentation][0].
+//!
+//! [0]: https://jsonlines.org/
+
+
+use std::io::{self, BufWriter, Write};
+
+use crate::compress::Finish;
+use crate::output::{Record, RecordWriter};
+
+
+pub struct JsonLinesWriter<W: Write> {
+    out: BufWriter<W>,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(out: W) -> JsonLinesWriter<W> {
+        JsonLinesWriter { out: BufWriter::new(out) }
+    }
+}
+
+impl<W: Finish + Send> RecordWriter for JsonLinesWriter<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, record)?;
+        writeln!(self.out)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.out.finish()
+    }
+}
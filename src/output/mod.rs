@@ -0,0 +1,346 @@
+//! Writers of the converted records, one per output format.
+//!
+//! The records are produced by the parsing workers, and sent to the writing
+//! worker which gives them to a `RecordWriter`.
+
+
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::compress::Compression;
+use crate::dgraph::Schema;
+use crate::links::{Degree, Link};
+use crate::pages::Page;
+use crate::rdf::{Flavor, RdfFormat, Statement};
+
+mod cypher;
+mod dot;
+mod gexf;
+mod graphml;
+mod json;
+mod jsonl;
+mod neo4j;
+mod ntriples;
+mod parquet;
+mod postgres;
+mod shards;
+mod sqlite;
+mod turtle;
+
+pub use shards::{parse_size, Sharding};
+
+
+/// A record extracted from the dumps. It is serialized with its fields,
+/// plus its kind in the `type` field.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Record {
+    Page(Page),
+    Link(Link),
+    Degree(Degree),
+}
+
+impl Record {
+    /// Convert a Record to RDF statements, made according to `rdf`. The
+    /// reverse of the links are included if `reverse` is true.
+    pub fn to_rdf(&self, rdf: &RdfFormat, reverse: bool) -> Vec<Statement> {
+        match self {
+            Record::Page(page) => page.to_rdf(rdf),
+            Record::Link(link) if reverse => vec![link.to_rdf(rdf), link.to_reverse_rdf(rdf)],
+            Record::Link(link) => vec![link.to_rdf(rdf)],
+            Record::Degree(degree) => degree.to_rdf(rdf),
+        }
+    }
+}
+
+/// Write records in a given format.
+pub trait RecordWriter: Send {
+    /// Write the `record`.
+    fn write(&mut self, record: &Record) -> io::Result<()>;
+
+    /// Write the reverse of the `link` only, for the formats holding the
+    /// reverse of the links.
+    fn write_reverse(&mut self, _link: &Link) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "this output format can't hold the reverse of the links"
+        ))
+    }
+
+    /// Write what remains and flush the output.
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+
+/// What an output holds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Content {
+    /// The pages.
+    Pages,
+    /// The links, and the degrees of the pages.
+    Links,
+}
+
+
+/// The output formats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// N-Triples, or N-Quads when the statements are in a graph.
+    NTriples,
+    /// Turtle, with the statements grouped by subject.
+    Turtle,
+    /// CSV for the Neo4j import tool.
+    Neo4j,
+    /// JSON mutations for the dgraph live loader, in several files.
+    Json,
+    /// JSON Lines, one record per line.
+    JsonLines,
+    /// Apache Parquet, with one column per field.
+    Parquet,
+    /// A SQLite database, with one table per kind of record.
+    Sqlite,
+    /// The text format of the PostgreSQL COPY command.
+    Postgres,
+    /// GraphML, with the pages as nodes and the links as edges.
+    GraphMl,
+    /// GEXF, with the pages as nodes and the links as edges.
+    Gexf,
+    /// Graphviz DOT, with the pages as nodes and the links as edges.
+    Dot,
+    /// A Cypher script, for cypher-shell.
+    Cypher,
+}
+
+impl Format {
+    /// Tell whether the format can have a dgraph schema.
+    pub fn has_schema(self) -> bool {
+        matches!(self, Format::NTriples | Format::Turtle | Format::Json)
+    }
+
+    /// Tell whether the format writes the page IDs as dgraph uids, in dgraph
+    /// flavor with `Ids::Uid`.
+    pub fn writes_uids(self) -> bool {
+        matches!(self, Format::NTriples | Format::Turtle | Format::Json)
+    }
+
+    /// Tell whether the format can hold the degrees of the pages along with
+    /// the links.
+    pub fn holds_degrees(self) -> bool {
+        matches!(
+            self,
+            Format::NTriples | Format::Turtle | Format::Json | Format::JsonLines | Format::Sqlite
+                | Format::Cypher
+        )
+    }
+
+    /// Tell whether the format can hold the reverse of the links, as the
+    /// `linkedfrom` predicate.
+    pub fn holds_reverse(self) -> bool {
+        matches!(self, Format::NTriples | Format::Turtle | Format::Json)
+    }
+
+    /// Tell whether the format is a graph file, which needs the pages (as
+    /// nodes) before the links.
+    pub fn needs_pages(self) -> bool {
+        matches!(self, Format::GraphMl | Format::Gexf | Format::Dot)
+    }
+
+    /// Tell whether the format can be split into shards, each one being a
+    /// file of the format on its own.
+    pub fn can_shard(self) -> bool {
+        matches!(
+            self,
+            Format::NTriples | Format::Turtle | Format::JsonLines | Format::Postgres
+                | Format::Cypher
+        )
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ntriples" => Ok(Format::NTriples),
+            "turtle" => Ok(Format::Turtle),
+            "neo4j" => Ok(Format::Neo4j),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::JsonLines),
+            "parquet" => Ok(Format::Parquet),
+            "sqlite" => Ok(Format::Sqlite),
+            "postgres" => Ok(Format::Postgres),
+            "graphml" => Ok(Format::GraphMl),
+            "gexf" => Ok(Format::Gexf),
+            "dot" => Ok(Format::Dot),
+            "cypher" => Ok(Format::Cypher),
+            _ => Err(format!(
+                "unknown format {}, expected ntriples, turtle, neo4j, json, jsonl, parquet, \
+                 sqlite, postgres, graphml, gexf, dot or cypher",
+                s
+            ))
+        }
+    }
+}
+
+
+/// The options of the writers.
+pub struct Options {
+    /// How to make the RDF terms.
+    pub rdf: RdfFormat,
+    /// Whether the reverse of the links are written too.
+    pub reverse: bool,
+    /// The dgraph schema to save along, if any.
+    pub schema: Option<Schema>,
+    /// The number of objects per file, or per statement, for the formats
+    /// written in batches. Each format has its own default.
+    pub batch_size: Option<usize>,
+    /// The path to write the script creating the SQL table to, if any.
+    pub ddl: Option<PathBuf>,
+    /// The compression of the files, for the formats written as streams.
+    pub compress: Compression,
+    /// How to split the output into shards, if it is.
+    pub sharding: Option<Sharding>,
+}
+
+/// Check the writer of the `format` can be made according to `options`,
+/// before reading anything.
+pub fn check(format: Format, options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.schema.is_some() && !format.has_schema() {
+        return Err("this output format can't have a dgraph schema".into());
+    }
+    if options.batch_size == Some(0) {
+        return Err("the batch size must be at least 1".into());
+    }
+    if options.reverse && !format.holds_reverse() {
+        return Err("this output format can't hold the reverse of the links".into());
+    }
+    if options.ddl.is_some() && format != Format::Postgres {
+        return Err("only the postgres format can have a DDL script".into());
+    }
+    if options.sharding.is_some() && !format.can_shard() {
+        return Err("this output format can't be split into shards".into());
+    }
+    if format == Format::Turtle && options.rdf.has_graph() {
+        return Err("the Turtle format can't hold a graph label".into());
+    }
+    let dgraph_triples = options.rdf.flavor() == Flavor::Dgraph && !options.rdf.has_graph();
+    if format == Format::Json && !dgraph_triples {
+        return Err("the JSON format needs the dgraph flavor, without graph".into());
+    }
+    Ok(())
+}
+
+/// Make the writer of the `format` to `path`, which will hold the `content`,
+/// according to `options`.
+pub fn create(
+    format: Format,
+    content: Content,
+    path: &Path,
+    mut options: Options
+) -> Result<Box<dyn RecordWriter>, Box<dyn Error>> {
+    check(format, &options)?;
+    if let Some(sharding) = options.sharding.take() {
+        if let Some(ddl) = options.ddl.take() {
+            postgres::write_ddl(&ddl, content)?;
+        }
+        return Ok(Box::new(shards::ShardedWriter::new(format, content, path, options, sharding)?));
+    }
+    let compress = options.compress.clone();
+    match format {
+        Format::NTriples => Ok(Box::new(ntriples::NTriplesWriter::new(compress.create(path)?, options))),
+        Format::Turtle => Ok(Box::new(turtle::TurtleWriter::new(compress.create(path)?, options)?)),
+        Format::Neo4j => Ok(Box::new(neo4j::Neo4jWriter::new(compress.create(path)?, content)?)),
+        Format::Json => Ok(Box::new(json::JsonWriter::new(path.to_path_buf(), options))),
+        Format::JsonLines => Ok(Box::new(jsonl::JsonLinesWriter::new(compress.create(path)?))),
+        Format::Parquet => Ok(Box::new(parquet::ParquetWriter::new(path, content)?)),
+        Format::Sqlite => Ok(Box::new(sqlite::SqliteWriter::new(path, content)?)),
+        Format::Postgres => {
+            if let Some(ddl) = &options.ddl {
+                postgres::write_ddl(ddl, content)?;
+            }
+            Ok(Box::new(postgres::PostgresWriter::new(compress.create(path)?)))
+        },
+        Format::GraphMl => Ok(Box::new(graphml::GraphMlWriter::new(compress.create(path)?)?)),
+        Format::Gexf => Ok(Box::new(gexf::GexfWriter::new(compress.create(path)?)?)),
+        Format::Dot => Ok(Box::new(dot::DotWriter::new(compress.create(path)?)?)),
+        Format::Cypher => Ok(Box::new(cypher::CypherWriter::new(compress.create(path)?, options)?)),
+    }
+}
+
+
+/// Return the path of the file number `n`, when the output is written in
+/// several files: `n` is inserted in the file name of `path` before its
+/// extensions (*e.g.* `out.json.gz` becomes `out-00001.json.gz`).
+pub fn numbered_path(path: &Path, n: usize) -> PathBuf {
+    let (stem, extensions) = split_file_name(path);
+    path.with_file_name(format!("{}-{:05}{}", stem, n, extensions))
+}
+
+/// Split the file name of `path` into its stem and its extensions, the
+/// latter starting with a dot (*e.g.* `out.json.gz` gives `out` and
+/// `.json.gz`). A leading dot is part of the stem.
+pub fn split_file_name(path: &Path) -> (String, String) {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let (stem, extensions) = match name.find('.') {
+        Some(0) | None => (name.as_ref(), ""),
+        Some(i) => name.split_at(i),
+    };
+    (stem.to_string(), extensions.to_string())
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::compress::Codec;
+    use crate::utils::temp_path;
+
+    /// Return the default options of the writers, in dgraph flavor and
+    /// without compression.
+    pub fn options() -> Options {
+        Options {
+            rdf: RdfFormat::new(Flavor::Dgraph, None, None, None, None).unwrap(),
+            reverse: false,
+            schema: None,
+            batch_size: None,
+            ddl: None,
+            compress: Compression::new(Codec::None, 1),
+            sharding: None,
+        }
+    }
+
+    pub fn page(pageid: u64, namespace: u32, title: &str, is_redirect: bool) -> Record {
+        Record::Page(Page { pageid, namespace, title: title.to_string(), is_redirect })
+    }
+
+    pub fn link(from_id: u64, to_id: u64) -> Record {
+        Record::Link(Link { from_id, from_namespace: 0, to_id, to_namespace: 0 })
+    }
+
+    /// Write the `records` to the file `name` with the writer of the
+    /// `format`, according to `options`, and return what was written.
+    pub fn write(
+        format: Format,
+        content: Content,
+        name: &str,
+        records: &[Record],
+        options: Options
+    ) -> String {
+        let path = temp_path(name);
+        let mut writer = create(format, content, &path, options).unwrap();
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        written
+    }
+}
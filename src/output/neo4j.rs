@@ -0,0 +1,64 @@
+//! Write the records as CSV files for `neo4j-admin database import`.
+//!
+//! The pages are written with the `pageid:ID,title,namespace:int,:LABEL`
+//! header, and labelled `Page` plus the name of their namespace (and
+//! `Redirect` for the redirects). The links are written with the
+//! `:START_ID,:END_ID,:TYPE` header, and typed `LINKS_TO`.
+//!
+//! See the import tool [documentation][0].
+//!
+//! [0]: https://neo4j.com/docs/operations-manual/current/tools/neo4j-admin/neo4j-admin-import/
+
+
+use std::io::{self, BufWriter, Write};
+
+use crate::compress::Finish;
+use crate::output::{Content, Record, RecordWriter};
+use crate::pages::namespace_name;
+use crate::utils::csv_quote;
+
+
+pub struct Neo4jWriter<W: Write> {
+    out: BufWriter<W>,
+}
+
+impl<W: Write> Neo4jWriter<W> {
+    /// Make a new writer, and write the header line of the `content` to
+    /// `out`.
+    pub fn new(out: W, content: Content) -> io::Result<Neo4jWriter<W>> {
+        let mut out = BufWriter::new(out);
+        match content {
+            Content::Pages => writeln!(out, "pageid:ID,title,namespace:int,:LABEL")?,
+            Content::Links => writeln!(out, ":START_ID,:END_ID,:TYPE")?,
+        }
+        Ok(Neo4jWriter { out })
+    }
+}
+
+impl<W: Finish + Send> RecordWriter for Neo4jWriter<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        match record {
+            Record::Page(page) => {
+                let mut labels = format!("Page;{}", namespace_name(page.namespace));
+                if page.is_redirect {
+                    labels.push_str(";Redirect");
+                }
+                writeln!(
+                    self.out, "{},{},{},{}",
+                    page.pageid, csv_quote(&page.title), page.namespace, labels
+                )
+            },
+            Record::Link(link) => {
+                writeln!(self.out, "{},{},LINKS_TO", link.from_id, link.to_id)
+            },
+            Record::Degree(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the neo4j format can't hold the degrees"
+            )),
+        }
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.out.finish()
+    }
+}
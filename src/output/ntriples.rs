@@ -0,0 +1,58 @@
+//! Write the records as N-Triples (or N-Quads), one statement per line.
+
+
+use std::io::{self, BufWriter, Write};
+
+use crate::dgraph::Schema;
+use crate::compress::Finish;
+use crate::links::Link;
+use crate::output::{Options, Record, RecordWriter};
+use crate::rdf::{RdfFormat, Statement};
+
+
+pub struct NTriplesWriter<W: Write> {
+    out: BufWriter<W>,
+    rdf: RdfFormat,
+    reverse: bool,
+    schema: Option<Schema>,
+}
+
+impl<W: Write> NTriplesWriter<W> {
+    pub fn new(out: W, options: Options) -> NTriplesWriter<W> {
+        NTriplesWriter {
+            out: BufWriter::new(out),
+            rdf: options.rdf,
+            reverse: options.reverse,
+            schema: options.schema,
+        }
+    }
+
+    fn write_statements(&mut self, statements: Vec<Statement>) -> io::Result<()> {
+        if let Some(schema) = &mut self.schema {
+            schema.add_statements(&statements);
+        }
+        for statement in &statements {
+            writeln!(self.out, "{}", statement)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Finish + Send> RecordWriter for NTriplesWriter<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        let statements = record.to_rdf(&self.rdf, self.reverse);
+        self.write_statements(statements)
+    }
+
+    fn write_reverse(&mut self, link: &Link) -> io::Result<()> {
+        let statements = vec![link.to_reverse_rdf(&self.rdf)];
+        self.write_statements(statements)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        if let Some(schema) = &self.schema {
+            schema.save()?;
+        }
+        self.out.finish()
+    }
+}
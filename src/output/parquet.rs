@@ -0,0 +1,171 @@
+//! Write the records as an Apache Parquet file.
+//!
+//! The pages are written with the `pageid` (unsigned 64 bits), `namespace`
+//! (unsigned 32 bits), `title` (UTF-8) and `is_redirect` (boolean) columns,
+//! and the links with the `from_id` and `to_id` columns. The records are
+//! buffered, then written in row groups of `ROW_GROUP_SIZE` rows, compressed
+//! with zstd.
+//!
+//! See the Parquet [documentation][0].
+//!
+//! [0]: https://parquet.apache.org/docs/file-format/
+
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, Int32Type, Int64Type};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::schema::parser::parse_message_type;
+
+use crate::output::{Content, Record, RecordWriter};
+
+
+/// The number of rows of a row group.
+const ROW_GROUP_SIZE: usize = 1_000_000;
+
+const PAGES_SCHEMA: &str = "
+    message page {
+        REQUIRED INT64 pageid (INTEGER(64, false));
+        REQUIRED INT32 namespace (INTEGER(32, false));
+        REQUIRED BYTE_ARRAY title (UTF8);
+        REQUIRED BOOLEAN is_redirect;
+    }
+";
+
+const LINKS_SCHEMA: &str = "
+    message link {
+        REQUIRED INT64 from_id (INTEGER(64, false));
+        REQUIRED INT64 to_id (INTEGER(64, false));
+    }
+";
+
+
+/// The buffered columns of the rows not written yet. The unsigned integers
+/// are stored as signed ones of the same size, as Parquet does.
+enum Columns {
+    Pages {
+        pageids: Vec<i64>,
+        namespaces: Vec<i32>,
+        titles: Vec<ByteArray>,
+        redirects: Vec<bool>,
+    },
+    Links {
+        from_ids: Vec<i64>,
+        to_ids: Vec<i64>,
+    },
+}
+
+impl Columns {
+    /// Return the number of buffered rows.
+    fn len(&self) -> usize {
+        match self {
+            Columns::Pages { pageids, .. } => pageids.len(),
+            Columns::Links { from_ids, .. } => from_ids.len(),
+        }
+    }
+
+    /// Write the buffered rows to the `row_group`, in the order of the
+    /// schema, and empty the buffers.
+    fn write(&mut self, row_group: &mut SerializedRowGroupWriter<'_, File>) -> Result<(), ParquetError> {
+        match self {
+            Columns::Pages { pageids, namespaces, titles, redirects } => {
+                write_column::<Int64Type>(row_group, pageids)?;
+                write_column::<Int32Type>(row_group, namespaces)?;
+                write_column::<ByteArrayType>(row_group, titles)?;
+                write_column::<BoolType>(row_group, redirects)?;
+            },
+            Columns::Links { from_ids, to_ids } => {
+                write_column::<Int64Type>(row_group, from_ids)?;
+                write_column::<Int64Type>(row_group, to_ids)?;
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Write the `values` to the next column of the `row_group`, and empty them.
+fn write_column<T: DataType>(
+    row_group: &mut SerializedRowGroupWriter<'_, File>,
+    values: &mut Vec<T::T>
+) -> Result<(), ParquetError> {
+    let mut column = row_group.next_column()?
+        .ok_or_else(|| ParquetError::General("too many columns".to_string()))?;
+    column.typed::<T>().write_batch(values, None, None)?;
+    values.clear();
+    column.close()
+}
+
+
+pub struct ParquetWriter {
+    writer: SerializedFileWriter<File>,
+    columns: Columns,
+}
+
+impl ParquetWriter {
+    /// Make a new writer of the `content` to the file at `path`.
+    pub fn new(path: &Path, content: Content) -> Result<ParquetWriter, ParquetError> {
+        let (schema, columns) = match content {
+            Content::Pages => (PAGES_SCHEMA, Columns::Pages {
+                pageids: vec![], namespaces: vec![], titles: vec![], redirects: vec![]
+            }),
+            Content::Links => (LINKS_SCHEMA, Columns::Links { from_ids: vec![], to_ids: vec![] }),
+        };
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        let writer = SerializedFileWriter::new(
+            File::create(path)?,
+            Arc::new(parse_message_type(schema)?),
+            Arc::new(properties)
+        )?;
+        Ok(ParquetWriter { writer, columns })
+    }
+
+    /// Write the buffered rows as a row group, if any.
+    fn write_row_group(&mut self) -> Result<(), ParquetError> {
+        if self.columns.len() == 0 {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group()?;
+        self.columns.write(&mut row_group)?;
+        row_group.close()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for ParquetWriter {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        match (record, &mut self.columns) {
+            (Record::Page(page), Columns::Pages { pageids, namespaces, titles, redirects }) => {
+                pageids.push(page.pageid as i64);
+                namespaces.push(page.namespace as i32);
+                titles.push(ByteArray::from(page.title.as_str()));
+                redirects.push(page.is_redirect);
+            },
+            (Record::Link(link), Columns::Links { from_ids, to_ids }) => {
+                from_ids.push(link.from_id as i64);
+                to_ids.push(link.to_id as i64);
+            },
+            _ => return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the parquet file can't hold this kind of record"
+            )),
+        }
+        if self.columns.len() == ROW_GROUP_SIZE {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.write_row_group()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! Write the records in the text format of the PostgreSQL `COPY` command.
+//!
+//! Each record is a line of tab-separated columns. The pages are written as
+//! rows of the `page` table, and the links as rows of the `link` table. The
+//! script creating the table can be written along, with the one adding its
+//! keys and indexes once the rows are copied.
+//!
+//! See the `COPY` [documentation][0].
+//!
+//! [0]: https://www.postgresql.org/docs/current/sql-copy.html
+
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::compress::Finish;
+use crate::output::{split_file_name, Content, Record, RecordWriter};
+
+
+/// The statements creating the tables, run before the rows are copied.
+const PAGE_TABLE: &str = "\
+CREATE TABLE page (
+    pageid bigint NOT NULL,
+    namespace integer NOT NULL,
+    title text NOT NULL,
+    is_redirect boolean NOT NULL
+);
+";
+
+const LINK_TABLE: &str = "\
+CREATE TABLE link (
+    from_id bigint NOT NULL,
+    to_id bigint NOT NULL
+);
+";
+
+/// The statements adding the keys and the indexes, run after the rows are
+/// copied, which is much faster than updating them row by row.
+const PAGE_INDEXES: &str = "\
+ALTER TABLE page ADD PRIMARY KEY (pageid);
+CREATE INDEX page_title ON page (namespace, title);
+";
+
+const LINK_INDEXES: &str = "\
+CREATE INDEX link_from ON link (from_id);
+CREATE INDEX link_to ON link (to_id);
+";
+
+
+pub struct PostgresWriter<W: Write> {
+    out: BufWriter<W>,
+}
+
+impl<W: Write> PostgresWriter<W> {
+    pub fn new(out: W) -> PostgresWriter<W> {
+        PostgresWriter { out: BufWriter::new(out) }
+    }
+}
+
+impl<W: Finish + Send> RecordWriter for PostgresWriter<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        match record {
+            Record::Page(page) => {
+                let redirect = if page.is_redirect { "t" } else { "f" };
+                writeln!(
+                    self.out, "{}\t{}\t{}\t{}",
+                    page.pageid, page.namespace, escape(&page.title), redirect
+                )
+            },
+            Record::Link(link) => writeln!(self.out, "{}\t{}", link.from_id, link.to_id),
+            Record::Degree(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the postgres format can't hold the degrees"
+            )),
+        }
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.out.finish()
+    }
+}
+
+
+/// Write the script creating the table of the `content` to `path`, and the
+/// one adding its keys and indexes, once the rows are copied, to the path
+/// given by `indexes_path`.
+pub fn write_ddl(path: &Path, content: Content) -> io::Result<()> {
+    let (table, indexes) = match content {
+        Content::Pages => (PAGE_TABLE, PAGE_INDEXES),
+        Content::Links => (LINK_TABLE, LINK_INDEXES),
+    };
+    File::create(path)?.write_all(table.as_bytes())?;
+    File::create(indexes_path(path))?.write_all(indexes.as_bytes())
+}
+
+/// Return the path of the script adding the keys and the indexes, when the
+/// one creating the table is written to `path`: `pages.sql` becomes
+/// `pages-indexes.sql`.
+pub fn indexes_path(path: &Path) -> PathBuf {
+    let (stem, extensions) = split_file_name(path);
+    path.with_file_name(format!("{}-indexes{}", stem, extensions))
+}
+
+/// Escape the text `value` for a column: the backslashes and the tabs, line
+/// feeds and carriage returns, which would be taken as delimiters. `\N`,
+/// which stands for NULL, can't appear then.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::output::tests::{link, options, page, write};
+    use crate::output::Format;
+    use crate::utils::temp_path;
+
+    #[test]
+    fn rows() {
+        let records = [page(1, 0, "A", false), page(2, 14, "C", true)];
+        let written = write(Format::Postgres, Content::Pages, "pg-pages", &records, options());
+        assert_eq!(written, "1\t0\tA\tf\n2\t14\tC\tt\n");
+        let records = [link(1, 2), link(2, 1)];
+        let written = write(Format::Postgres, Content::Links, "pg-links", &records, options());
+        assert_eq!(written, "1\t2\n2\t1\n");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("plain title"), "plain title");
+        assert_eq!(escape(r"a\b"), r"a\\b");
+        assert_eq!(escape("a\tb\nc\rd"), r"a\tb\nc\rd");
+        assert_eq!(escape(r"\N"), r"\\N");
+    }
+
+    #[test]
+    fn ddl() {
+        let path = temp_path("postgres-ddl.sql");
+        write_ddl(&path, Content::Links).unwrap();
+        let indexes = indexes_path(&path);
+        assert_eq!(indexes, temp_path("postgres-ddl-indexes.sql"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), LINK_TABLE);
+        assert_eq!(fs::read_to_string(&indexes).unwrap(), LINK_INDEXES);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&indexes).unwrap();
+        assert!(!PAGE_TABLE.contains("PRIMARY KEY"));
+        assert!(PAGE_INDEXES.starts_with("ALTER TABLE page ADD PRIMARY KEY (pageid);"));
+    }
+}
@@ -0,0 +1,339 @@
+//! Write the records to several files, the shards, to be loaded in parallel.
+//!
+//! The shards are numbered like the files of the json format: `out.rdf.gz`
+//! becomes `out-00001.rdf.gz`, `out-00002.rdf.gz`, and so on. Either there
+//! is a fixed number of shards, and each record goes to the shard given by
+//! the hash of its subject, or the shards are written one after the other,
+//! up to a given size.
+//!
+//! Either way, the records about the same page go to the same shard (by
+//! size, as long as they follow each other), and a manifest listing the
+//! shards, one path per line, is written along: `out.rdf.gz` becomes
+//! `out-manifest.txt`. By count, the reverse of a link is about the page it
+//! goes to, so it goes to the shard of this page, not the one of the link.
+//!
+//! By count, all the shards are written at once, so they share the same
+//! compression threads.
+
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use fnv::FnvHasher;
+
+use crate::compress::Compression;
+use crate::dgraph::Schema;
+use crate::output::{
+    create, numbered_path, split_file_name, Content, Format, Options, Record, RecordWriter
+};
+use crate::rdf::RdfFormat;
+
+
+/// The number of records written between two checks of the size of the
+/// current shard.
+const SIZE_CHECK_INTERVAL: usize = 10_000;
+
+
+/// How to split the output into shards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sharding {
+    /// This number of shards, by hash of the subjects.
+    Count(usize),
+    /// Shards of about this number of bytes, once compressed.
+    Size(u64),
+}
+
+/// Parse a size in bytes, with an optional unit: `500MB`, `1GB`, `2GiB`...
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let i = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(i);
+    let number: u64 = number.parse().map_err(|_| format!("invalid size {}", s))?;
+    let factor: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        "KIB" => 1 << 10,
+        "MIB" => 1 << 20,
+        "GIB" => 1 << 30,
+        "TIB" => 1 << 40,
+        _ => return Err(format!(
+            "unknown size unit {}, expected B, KB, MB, GB, TB, KiB, MiB, GiB or TiB", unit
+        )),
+    };
+    number.checked_mul(factor).ok_or_else(|| format!("size too large: {}", s))
+}
+
+
+/// Write the records to the shards, each one with the writer of the format.
+pub struct ShardedWriter {
+    format: Format,
+    content: Content,
+    /// The path the paths of the shards are made from.
+    path: PathBuf,
+    rdf: RdfFormat,
+    reverse: bool,
+    batch_size: Option<usize>,
+    compress: Compression,
+    /// The dgraph schema of all the shards, as theirs would be saved to the
+    /// same file.
+    schema: Option<Schema>,
+    sharding: Sharding,
+    /// The writers of the shards: all of them by count, the current one by
+    /// size.
+    shards: Vec<Box<dyn RecordWriter>>,
+    /// The paths of the shards opened so far.
+    paths: Vec<PathBuf>,
+    /// The number of records written to the current shard, by size.
+    records: usize,
+    /// The subject of the last record written, by size.
+    subject: Option<u64>,
+}
+
+impl ShardedWriter {
+    /// Make the writer of the shards of the `format` made from `path`,
+    /// which will hold the `content`, according to `options`.
+    pub fn new(
+        format: Format,
+        content: Content,
+        path: &Path,
+        options: Options,
+        sharding: Sharding
+    ) -> Result<ShardedWriter, Box<dyn Error>> {
+        let mut writer = ShardedWriter {
+            format,
+            content,
+            path: path.to_path_buf(),
+            rdf: options.rdf,
+            reverse: options.reverse,
+            batch_size: options.batch_size,
+            // By count, all the shards are written at once.
+            compress: match sharding {
+                Sharding::Count(_) => options.compress.shared(),
+                Sharding::Size(_) => options.compress,
+            },
+            schema: options.schema,
+            sharding,
+            shards: vec![],
+            paths: vec![],
+            records: 0,
+            subject: None,
+        };
+        let shards = match sharding {
+            Sharding::Count(n) => n,
+            Sharding::Size(_) => 1,
+        };
+        for _ in 0..shards {
+            let shard = writer.open()?;
+            writer.shards.push(shard);
+        }
+        Ok(writer)
+    }
+
+    /// Open the next shard.
+    fn open(&mut self) -> Result<Box<dyn RecordWriter>, Box<dyn Error>> {
+        let path = numbered_path(&self.path, self.paths.len() + 1);
+        // By count, the reverse of the links are written apart, see `write`.
+        let reverse = self.reverse && matches!(self.sharding, Sharding::Size(_));
+        let options = Options {
+            rdf: self.rdf.clone(),
+            reverse,
+            schema: None,
+            batch_size: self.batch_size,
+            ddl: None,
+            compress: self.compress.clone(),
+            sharding: None,
+        };
+        let shard = create(self.format, self.content, &path, options)?;
+        self.paths.push(path);
+        Ok(shard)
+    }
+
+    /// Finish the current shard, and open the next one, if the current one
+    /// is full and the `subject` isn't the one of the last record.
+    fn rotate(&mut self, max_size: u64, subject: u64) -> io::Result<()> {
+        let same_subject = self.subject.replace(subject) == Some(subject);
+        if same_subject || self.records < SIZE_CHECK_INTERVAL {
+            return Ok(());
+        }
+        self.records = 0;
+        let current = self.paths.last().expect("no shard opened");
+        if fs::metadata(current)?.len() < max_size {
+            return Ok(());
+        }
+        if let Some(shard) = self.shards.pop() {
+            shard.finish()?;
+        }
+        let shard = self.open().map_err(|e| io::Error::other(e.to_string()))?;
+        self.shards.push(shard);
+        Ok(())
+    }
+}
+
+impl RecordWriter for ShardedWriter {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        if let Some(schema) = &mut self.schema {
+            schema.add_statements(&record.to_rdf(&self.rdf, self.reverse));
+        }
+        let subject = subject(record);
+        match self.sharding {
+            Sharding::Count(n) => {
+                let shard = shard_of(subject, n);
+                self.shards[shard].write(record)?;
+                // The reverse of the link is about the page it goes to.
+                if let (Record::Link(link), true) = (record, self.reverse) {
+                    let shard = shard_of(link.to_id, n);
+                    self.shards[shard].write_reverse(link)?;
+                }
+                Ok(())
+            },
+            Sharding::Size(max_size) => {
+                self.rotate(max_size, subject)?;
+                self.records += 1;
+                self.shards[0].write(record)
+            },
+        }
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        for shard in self.shards {
+            shard.finish()?;
+        }
+        let mut manifest = BufWriter::new(File::create(manifest_path(&self.path))?);
+        for path in &self.paths {
+            writeln!(manifest, "{}", path.display())?;
+        }
+        manifest.flush()?;
+        if let Some(schema) = &self.schema {
+            schema.save()?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Return the ID of the page the `record` is about: the page itself, the
+/// source of the link, or the page of the degrees.
+fn subject(record: &Record) -> u64 {
+    match record {
+        Record::Page(page) => page.pageid,
+        Record::Link(link) => link.from_id,
+        Record::Degree(degree) => degree.pageid,
+    }
+}
+
+/// Return the shard of the records about the page `pageid`, out of `n`.
+fn shard_of(pageid: u64, n: usize) -> usize {
+    let mut hasher = FnvHasher::default();
+    hasher.write_u64(pageid);
+    (hasher.finish() % n as u64) as usize
+}
+
+/// Return the path of the manifest of the shards made from `path`:
+/// `out.rdf.gz` becomes `out-manifest.txt`.
+fn manifest_path(path: &Path) -> PathBuf {
+    let (stem, _) = split_file_name(path);
+    path.with_file_name(format!("{}-manifest.txt", stem))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader};
+
+    use crate::compress::{self, Codec};
+    use crate::links::Link;
+    use crate::rdf::{Flavor, Statement};
+    use crate::utils::temp_path;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1234"), Ok(1234));
+        assert_eq!(parse_size("10B"), Ok(10));
+        assert_eq!(parse_size("500MB"), Ok(500_000_000));
+        assert_eq!(parse_size(" 2 gb "), Ok(2_000_000_000));
+        assert_eq!(parse_size("1KiB"), Ok(1024));
+        assert_eq!(parse_size("3MiB"), Ok(3 << 20));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_size("1TiB"), Ok(1 << 40));
+        assert_eq!(parse_size("1TB"), Ok(1_000_000_000_000));
+    }
+
+    #[test]
+    fn size_errors() {
+        assert_eq!(parse_size(""), Err("invalid size ".to_string()));
+        assert_eq!(parse_size("MB"), Err("invalid size MB".to_string()));
+        assert_eq!(parse_size("-1MB"), Err("invalid size -1MB".to_string()));
+        assert!(parse_size("1.5GB").unwrap_err().starts_with("unknown size unit .5GB"));
+        assert!(parse_size("10PB").unwrap_err().starts_with("unknown size unit PB"));
+        assert_eq!(parse_size("20000000TiB"), Err("size too large: 20000000TiB".to_string()));
+    }
+
+    #[test]
+    fn paths() {
+        let path = Path::new("out/links.rdf.gz");
+        assert_eq!(numbered_path(path, 1), Path::new("out/links-00001.rdf.gz"));
+        assert_eq!(numbered_path(path, 123456), Path::new("out/links-123456.rdf.gz"));
+        assert_eq!(manifest_path(path), Path::new("out/links-manifest.txt"));
+        assert_eq!(numbered_path(Path::new("links"), 2), Path::new("links-00002"));
+        assert_eq!(manifest_path(Path::new("links")), Path::new("links-manifest.txt"));
+        assert_eq!(numbered_path(Path::new(".links.nt"), 3), Path::new(".links.nt-00003"));
+        assert_eq!(manifest_path(Path::new(".links.nt")), Path::new(".links.nt-manifest.txt"));
+    }
+
+    #[test]
+    fn reverse_links_by_count() {
+        let dir = temp_path("shards");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("links.nt");
+        let rdf = RdfFormat::new(Flavor::Dgraph, None, None, None, None).unwrap();
+        let options = Options {
+            rdf: rdf.clone(),
+            reverse: true,
+            schema: None,
+            batch_size: None,
+            ddl: None,
+            compress: Compression::new(Codec::None, 1),
+            sharding: None,
+        };
+        let sharding = Sharding::Count(4);
+        let writer = ShardedWriter::new(Format::NTriples, Content::Links, &path, options, sharding);
+        let mut writer = Box::new(writer.unwrap());
+        let mut expected = vec![];
+        for from_id in 1..50 {
+            for to_id in &[from_id * 7 % 50, from_id * 13 % 50] {
+                let link = Link { from_id, from_namespace: 0, to_id: *to_id, to_namespace: 0 };
+                expected.push(link.to_rdf(&rdf).to_string());
+                expected.push(link.to_reverse_rdf(&rdf).to_string());
+                writer.write(&Record::Link(link)).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+
+        // Each subject is in a single shard, and all the statements are.
+        let mut shards: HashMap<String, PathBuf> = HashMap::new();
+        let mut statements = vec![];
+        let manifest = fs::read_to_string(manifest_path(&path)).unwrap();
+        assert_eq!(manifest.lines().count(), 4);
+        for shard in manifest.lines().map(PathBuf::from) {
+            for line in BufReader::new(compress::open(&shard).unwrap()).lines() {
+                let line = line.unwrap();
+                let subject = Statement::parse(&line).unwrap().subject.to_string();
+                assert_eq!(shards.entry(subject).or_insert_with(|| shard.clone()), &shard);
+                statements.push(line);
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        expected.sort();
+        statements.sort();
+        assert_eq!(statements, expected);
+    }
+}
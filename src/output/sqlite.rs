@@ -0,0 +1,116 @@
+//! Write the records to a SQLite database.
+//!
+//! The pages are written to the `page` table, the links to the `link` table
+//! and the degrees to the `degree` table. The tables of the content are
+//! created again if they exist, so the pages and the links can be written to
+//! the same database. The rows are inserted in transactions of
+//! `TRANSACTION_SIZE` rows, and the indexes are created at the end.
+
+
+use std::io;
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::output::{Content, Record, RecordWriter};
+
+
+/// The number of rows inserted per transaction.
+const TRANSACTION_SIZE: usize = 100_000;
+
+const PAGE_TABLE: &str = "
+    DROP TABLE IF EXISTS page;
+    CREATE TABLE page (
+        pageid INTEGER PRIMARY KEY,
+        namespace INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        is_redirect INTEGER NOT NULL
+    );
+";
+
+const PAGE_INDEXES: &str = "
+    CREATE INDEX page_title ON page (namespace, title);
+";
+
+const LINK_TABLES: &str = "
+    DROP TABLE IF EXISTS link;
+    CREATE TABLE link (
+        from_id INTEGER NOT NULL,
+        to_id INTEGER NOT NULL
+    );
+    DROP TABLE IF EXISTS degree;
+    CREATE TABLE degree (
+        pageid INTEGER PRIMARY KEY,
+        outdegree INTEGER NOT NULL,
+        indegree INTEGER NOT NULL
+    );
+";
+
+const LINK_INDEXES: &str = "
+    CREATE INDEX link_from ON link (from_id);
+    CREATE INDEX link_to ON link (to_id);
+";
+
+
+pub struct SqliteWriter {
+    connection: Connection,
+    content: Content,
+    /// The number of rows inserted in the current transaction.
+    rows: usize,
+}
+
+impl SqliteWriter {
+    /// Open the database at `path`, create the tables of the `content` and
+    /// start the first transaction.
+    pub fn new(path: &Path, content: Content) -> rusqlite::Result<SqliteWriter> {
+        let connection = Connection::open(path)?;
+        // The database is only written once, so there's no need to be able
+        // to recover it if the conversion fails.
+        connection.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;")?;
+        match content {
+            Content::Pages => connection.execute_batch(PAGE_TABLE)?,
+            Content::Links => connection.execute_batch(LINK_TABLES)?,
+        }
+        connection.execute_batch("BEGIN")?;
+        Ok(SqliteWriter { connection, content, rows: 0 })
+    }
+
+    fn insert(&mut self, record: &Record) -> rusqlite::Result<()> {
+        match record {
+            Record::Page(page) => self.connection
+                .prepare_cached("INSERT INTO page VALUES (?1, ?2, ?3, ?4)")?
+                .execute(params![page.pageid, page.namespace, page.title, page.is_redirect])?,
+            Record::Link(link) => self.connection
+                .prepare_cached("INSERT INTO link VALUES (?1, ?2)")?
+                .execute(params![link.from_id, link.to_id])?,
+            Record::Degree(degree) => self.connection
+                .prepare_cached("INSERT INTO degree VALUES (?1, ?2, ?3)")?
+                .execute(params![degree.pageid, degree.outdegree, degree.indegree])?,
+        };
+        self.rows += 1;
+        if self.rows == TRANSACTION_SIZE {
+            self.connection.execute_batch("COMMIT; BEGIN")?;
+            self.rows = 0;
+        }
+        Ok(())
+    }
+
+    /// Commit the last transaction and create the indexes.
+    fn close(&self) -> rusqlite::Result<()> {
+        self.connection.execute_batch("COMMIT")?;
+        match self.content {
+            Content::Pages => self.connection.execute_batch(PAGE_INDEXES),
+            Content::Links => self.connection.execute_batch(LINK_INDEXES),
+        }
+    }
+}
+
+impl RecordWriter for SqliteWriter {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        self.insert(record).map_err(io::Error::other)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.close().map_err(io::Error::other)
+    }
+}
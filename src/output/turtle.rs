@@ -0,0 +1,273 @@
+//! Write the records as Turtle.
+//!
+//! The prefixes of the IRIs are declared at the beginning of the file, and
+//! the consecutive statements about the same subject are grouped in one
+//! block, separated by `;` (or by `,` when they also share the predicate).
+//! The reverse of the links are about their targets, so they're kept until
+//! the links from the same source are written, not to split their block.
+//!
+//! See the Turtle [specification][0].
+//!
+//! [0]: https://www.w3.org/TR/turtle/
+
+
+use std::io::{self, BufWriter, Write};
+
+use crate::dgraph::Schema;
+use crate::compress::Finish;
+use crate::links::Link;
+use crate::output::{Options, Record, RecordWriter};
+use crate::rdf::{RdfFormat, Statement, Term, XSD, escape};
+
+
+pub struct TurtleWriter<W: Write> {
+    out: BufWriter<W>,
+    rdf: RdfFormat,
+    reverse: bool,
+    schema: Option<Schema>,
+    /// The prefixes of the IRIs, with their names.
+    prefixes: Vec<(String, String)>,
+    /// The subject and predicate of the last statement written, if any.
+    last: Option<(Term, Term)>,
+    /// The source of the last links, and their reverse not written yet.
+    reverse_links: Option<(u64, Vec<Statement>)>,
+}
+
+impl<W: Write> TurtleWriter<W> {
+    /// Make a new writer, and write the prefixes declaration to `out`.
+    pub fn new(out: W, options: Options) -> io::Result<TurtleWriter<W>> {
+        let mut out = BufWriter::new(out);
+        let prefixes: Vec<(String, String)> = options.rdf.prefixes().into_iter()
+            .map(|(name, iri)| (name.to_string(), iri.to_string()))
+            .collect();
+        for (name, iri) in &prefixes {
+            writeln!(out, "@prefix {}: <{}> .", name, iri)?;
+        }
+        if !prefixes.is_empty() {
+            writeln!(out)?;
+        }
+        Ok(TurtleWriter {
+            out,
+            rdf: options.rdf,
+            reverse: options.reverse,
+            schema: options.schema,
+            prefixes,
+            last: None,
+            reverse_links: None,
+        })
+    }
+
+    /// Write the `term`, using the prefixes when possible.
+    fn term(&self, term: &Term) -> String {
+        match term {
+            Term::Iri(iri) => self.compact(iri).unwrap_or_else(|| term.to_string()),
+            Term::Literal {value, datatype: Some(datatype), ..} => {
+                match datatype.strip_prefix(XSD) {
+                    Some("integer") | Some("boolean") => value.clone(),
+                    _ => format!(
+                        "\"{}\"^^{}",
+                        escape(value),
+                        self.compact(datatype).unwrap_or_else(|| format!("<{}>", datatype))
+                    ),
+                }
+            },
+            _ => term.to_string()
+        }
+    }
+
+    /// Write the `iri` as a prefixed name, if one of the prefixes matches
+    /// and the rest is a valid local name.
+    fn compact(&self, iri: &str) -> Option<String> {
+        self.prefixes.iter()
+            .filter_map(|(name, prefix)| Some((name, iri.strip_prefix(prefix.as_str())?)))
+            .find(|(_, local)| is_local_name(local))
+            .map(|(name, local)| format!("{}:{}", name, local))
+    }
+
+    /// Keep the reverse of the `link` until the links from its source are
+    /// written.
+    fn add_reverse(&mut self, link: &Link) -> io::Result<()> {
+        let reverse = link.to_reverse_rdf(&self.rdf);
+        match &mut self.reverse_links {
+            Some((from_id, statements)) if *from_id == link.from_id => statements.push(reverse),
+            _ => {
+                self.write_reverse_links()?;
+                self.reverse_links = Some((link.from_id, vec![reverse]));
+            },
+        }
+        Ok(())
+    }
+
+    /// Write the reverse of the links kept so far.
+    fn write_reverse_links(&mut self) -> io::Result<()> {
+        match self.reverse_links.take() {
+            Some((_, statements)) => self.write_statements(statements),
+            None => Ok(()),
+        }
+    }
+
+    fn write_statements(&mut self, statements: Vec<Statement>) -> io::Result<()> {
+        if let Some(schema) = &mut self.schema {
+            schema.add_statements(&statements);
+        }
+        for statement in statements {
+            self.write_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn write_statement(&mut self, statement: Statement) -> io::Result<()> {
+        let object = self.term(&statement.object);
+        match &self.last {
+            Some((subject, predicate))
+                if *subject == statement.subject && *predicate == statement.predicate => {
+                write!(self.out, ",\n        {}", object)?;
+            },
+            Some((subject, _)) if *subject == statement.subject => {
+                let predicate = self.term(&statement.predicate);
+                write!(self.out, " ;\n    {} {}", predicate, object)?;
+            },
+            last => {
+                if last.is_some() {
+                    writeln!(self.out, " .")?;
+                }
+                let subject = self.term(&statement.subject);
+                let predicate = self.term(&statement.predicate);
+                write!(self.out, "{} {} {}", subject, predicate, object)?;
+            }
+        }
+        self.last = Some((statement.subject, statement.predicate));
+        Ok(())
+    }
+}
+
+impl<W: Finish + Send> RecordWriter for TurtleWriter<W> {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        let source = match record {
+            Record::Link(link) => Some(link.from_id),
+            _ => None,
+        };
+        if source.is_none() || source != self.reverse_links.as_ref().map(|(from_id, _)| *from_id) {
+            self.write_reverse_links()?;
+        }
+        let statements = record.to_rdf(&self.rdf, false);
+        self.write_statements(statements)?;
+        match record {
+            Record::Link(link) if self.reverse => self.add_reverse(link),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_reverse(&mut self, link: &Link) -> io::Result<()> {
+        self.add_reverse(link)
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.write_reverse_links()?;
+        if let Some(schema) = &self.schema {
+            schema.save()?;
+        }
+        if self.last.is_some() {
+            writeln!(self.out, " .")?;
+        }
+        self.out.finish()
+    }
+}
+
+
+/// Tell whether `local` can be written as the local part of a prefixed
+/// name without escaping.
+fn is_local_name(local: &str) -> bool {
+    !local.is_empty()
+        && !local.starts_with(['.', '-'])
+        && !local.ends_with('.')
+        && local.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::output::tests::{link, options, page, write};
+    use crate::output::{Content, Format};
+    use crate::rdf::Flavor;
+
+    #[test]
+    fn grouped_by_subject() {
+        let records = [page(1, 0, "A \"b\"", false), page(2, 14, "C", true)];
+        let written = write(Format::Turtle, Content::Pages, "turtle-pages", &records, options());
+        assert_eq!(written, "\
+<1> <namespace> \"0\" ;
+    <title> \"A \\\"b\\\"\" .
+<2> <namespace> \"14\" ;
+    <title> \"C\" ;
+    <redirect> \"true\" .
+");
+    }
+
+    #[test]
+    fn grouped_by_predicate() {
+        let records = [link(1, 2), link(1, 3), link(2, 1)];
+        let written = write(Format::Turtle, Content::Links, "turtle-links", &records, options());
+        assert_eq!(written, "\
+<1> <linksto> <2>,
+        <3> .
+<2> <linksto> <1> .
+");
+    }
+
+    #[test]
+    fn reverse_after_their_source() {
+        let records = [link(1, 2), link(1, 3), link(2, 1), link(2, 3), link(1, 2)];
+        let options = Options { reverse: true, ..options() };
+        let written = write(Format::Turtle, Content::Links, "turtle-reverse", &records, options);
+        assert_eq!(written, "\
+<1> <linksto> <2>,
+        <3> .
+<2> <linkedfrom> <1> .
+<3> <linkedfrom> <1> .
+<2> <linksto> <1>,
+        <3> .
+<1> <linkedfrom> <2> .
+<3> <linkedfrom> <2> .
+<1> <linksto> <2> .
+<2> <linkedfrom> <1> .
+");
+    }
+
+    #[test]
+    fn prefixes() {
+        let rdf = RdfFormat::new(
+            Flavor::Strict, Some("https://fr.wikipedia.org/?curid=".to_string()), None,
+            Some("fr".to_string()), None
+        ).unwrap();
+        let records = [page(1, 0, "A", false), page(2, 14, "C", true)];
+        let options = Options { rdf, ..options() };
+        let written = write(Format::Turtle, Content::Pages, "turtle-prefixes", &records, options);
+        assert_eq!(written, "\
+@prefix page: <https://fr.wikipedia.org/?curid=> .
+@prefix vocab: <https://github.com/Picani/wikipedia-dump-converter/vocab#> .
+@prefix xsd: <http://www.w3.org/2001/XMLSchema#> .
+
+page:1 vocab:namespace 0 ;
+    vocab:title \"A\"@fr .
+page:2 vocab:namespace 14 ;
+    vocab:title \"C\"@fr ;
+    vocab:redirect true .
+");
+    }
+
+    #[test]
+    fn local_names() {
+        assert!(is_local_name("123"));
+        assert!(is_local_name("in-degree_2.b"));
+        assert!(!is_local_name(""));
+        assert!(!is_local_name(".a"));
+        assert!(!is_local_name("-a"));
+        assert!(!is_local_name("a."));
+        assert!(!is_local_name("a/b"));
+        assert!(!is_local_name("a?b"));
+        assert!(!is_local_name("é"));
+    }
+}
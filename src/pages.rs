@@ -8,18 +8,25 @@
 use std::fmt;
 use std::error::Error;
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::io::BufRead;
+use std::str::FromStr;
 
-use regex::Regex;
+use fnv::FnvHashMap;
+use serde::Serialize;
+
+use crate::dgraph::{Ids, PAGE_TYPE, TYPE_PREDICATE, XID_PREDICATE};
+use crate::rdf::{RdfFormat, Statement, Term};
 use crate::utils::clean_title;
 
 
 /// Represent a Wikipedia page.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Page {
     pub pageid: u64,
     pub namespace: u32,
-    pub title: String
+    pub title: String,
+    pub is_redirect: bool,
 }
 
 impl fmt::Display for Page {
@@ -42,127 +49,309 @@ impl Page {
         let namespace = values[1].parse::<u32>().or(
             Err(PageError::SQL { values: format!("{:?}", values) })
         )?;
+        let is_redirect = match values[4].as_str() {
+            "0" => false,
+            "1" => true,
+            _ => return Err(PageError::SQL { values: format!("{:?}", values) })
+        };
 
         Ok(Page{
             pageid: id,
             namespace,
             title: clean_title(&values[2]),
+            is_redirect,
         })
     }
 
-    /// Make a Page from the given RDF `triples`.
-    ///
-    /// Only the first two valid lines are taken into account. The comments
-    /// and the other lines are ignored (they're not even parsed, so no error
-    /// checking is done on them).
-    pub fn from_rdf(triples: Vec<String>) -> Result<Page, PageError> {
-        // We clean the input
-        let triples: Result<Vec<&str>, PageError> = triples.iter()
-            .filter(|l| !l.is_empty() && !l.starts_with("#"))
-            .map(|l| l.trim())
-            .map(|l|
-                if !l.starts_with("<") || !l.ends_with(".") {
-                    Err(PageError::RDF {triples: format!("{:?}", triples)})
-                } else { Ok(l) })
-            .collect();
-        let triples = triples?;
-
-        if triples.len() < 2 {
-            return Err(PageError::RDF {triples: format!("{:?}", triples)});
+    /// Convert a Page to two RDF triples, made according to `rdf`. Add one
+    /// triple if the page is a redirect, one for the type of the node and
+    /// one for its external ID if `rdf` tells so.
+    pub fn to_rdf(&self, rdf: &RdfFormat) -> Vec<Statement> {
+        let mut triples = vec![
+            rdf.statement(
+                rdf.node(self.pageid), rdf.predicate("namespace"), rdf.integer(self.namespace)
+            ),
+            rdf.statement(rdf.node(self.pageid), rdf.predicate("title"), rdf.title(&self.title)),
+        ];
+        if self.is_redirect {
+            triples.push(
+                rdf.statement(rdf.node(self.pageid), rdf.predicate("redirect"), rdf.boolean(true))
+            );
+        }
+        if rdf.types() {
+            triples.push(rdf.statement(
+                rdf.node(self.pageid), rdf.predicate(TYPE_PREDICATE), rdf.string(PAGE_TYPE)
+            ));
+        }
+        if rdf.ids() == Ids::Xid {
+            triples.push(rdf.statement(
+                rdf.node(self.pageid),
+                rdf.predicate(XID_PREDICATE),
+                rdf.string(&rdf.label(self.pageid))
+            ));
         }
+        triples
+    }
+}
 
-        let first_triple = match_triple(triples[0])
-            .ok_or(PageError::RDF {triples: format!("{:?}", triples)})?;
-        let second_triple = match_triple(triples[1])
-            .ok_or(PageError::RDF {triples: format!("{:?}", triples)})?;
 
-        if first_triple[0] != second_triple[0] {
-            return Err(PageError::RDF {triples: format!("{:?}", triples)});
-        }
+/// The English names of the namespaces, usable as identifiers (*i.e.*
+/// without spaces). See the list of [namespaces][0].
+///
+/// [0]: https://www.mediawiki.org/wiki/Manual:Namespace
+pub const NAMESPACE_NAMES: &[(u32, &str)] = &[
+    (0, "Article"),
+    (1, "Talk"),
+    (2, "User"),
+    (3, "UserTalk"),
+    (4, "Project"),
+    (5, "ProjectTalk"),
+    (6, "File"),
+    (7, "FileTalk"),
+    (8, "MediaWiki"),
+    (9, "MediaWikiTalk"),
+    (10, "Template"),
+    (11, "TemplateTalk"),
+    (12, "Help"),
+    (13, "HelpTalk"),
+    (14, "Category"),
+    (15, "CategoryTalk"),
+    (100, "Portal"),
+    (101, "PortalTalk"),
+    (118, "Draft"),
+    (119, "DraftTalk"),
+    (710, "TimedText"),
+    (711, "TimedTextTalk"),
+    (828, "Module"),
+    (829, "ModuleTalk"),
+];
 
-        let id: u64 = first_triple[0].parse().or(
-            Err(PageError::RDF { triples: format!("{:?}", triples) })
-        )?;
-        let namespace: u32;
-        let title: String;
-
-        if first_triple[1].as_str() == "namespace" {
-            namespace = first_triple[2].parse().or(
-                Err(PageError::RDF { triples: format!("{:?}", triples) })
-            )?;
-            title = second_triple[2].clone();
-        } else {
-            namespace = second_triple[2].parse().or(
-                Err(PageError::RDF { triples: format!("{:?}", triples) })
-            )?;
-            title = first_triple[2].clone();
+/// Return the English name of the `namespace`, from `NAMESPACE_NAMES`, or
+/// `Namespace` followed by its number if it has none.
+pub fn namespace_name(namespace: u32) -> String {
+    match NAMESPACE_NAMES.iter().find(|(n, _)| *n == namespace) {
+        Some((_, name)) => name.to_string(),
+        None => format!("Namespace{}", namespace)
+    }
+}
+
+
+/// The number of title collisions reported one by one, as there can be many.
+const COLLISION_EXAMPLES: usize = 5;
+
+
+/// Accumulate the values read from the RDF triples about one page.
+struct PageBuilder {
+    pageid: u64,
+    namespace: Option<u32>,
+    title: Option<String>,
+    is_redirect: Option<bool>,
+}
+
+impl PageBuilder {
+    /// Set the `field` named `predicate` to `value`.
+    ///
+    /// Return `Ok(true)` if the field was already set to the same value,
+    /// and an error if it was set to a different value.
+    fn set<T: PartialEq + fmt::Display>(
+        pageid: u64,
+        predicate: &str,
+        field: &mut Option<T>,
+        value: T
+    ) -> Result<bool, PageError> {
+        match field {
+            None => {
+                *field = Some(value);
+                Ok(false)
+            },
+            Some(v) if *v == value => Ok(true),
+            Some(v) => Err(PageError::Conflict {
+                pageid,
+                predicate: predicate.to_string(),
+                first: v.to_string(),
+                second: value.to_string()
+            })
         }
+    }
 
-        Ok( Page { pageid: id, namespace, title } )
+    fn build(self) -> Result<Page, PageError> {
+        match (self.namespace, self.title) {
+            (Some(namespace), Some(title)) =>
+                Ok(Page {
+                    pageid: self.pageid,
+                    namespace,
+                    title,
+                    is_redirect: self.is_redirect.unwrap_or(false)
+                }),
+            _ => Err(PageError::Incomplete { pageid: self.pageid })
+        }
     }
+}
 
-    /// Convert a Page to two RDF triples.
-    ///
-    /// Return them as an unique String, the two triples separated by a
-    /// newline character.
-    pub fn to_rdf(&self) -> String {
-        format!(
-            "<{}> <namespace> \"{}\" .\n<{}> <title> \"{}\" .",
-            self.pageid, self.namespace, self.pageid, self.title
-        )
+/// What to do when several pages share the same title and namespace.
+#[derive(Clone, Copy, Debug)]
+pub enum DuplicatePolicy {
+    /// Keep the page that comes first in the file.
+    KeepFirst,
+    /// Keep the page with the lowest ID.
+    KeepLowestId,
+    /// Keep the page that isn't a redirect, or the first one if both are
+    /// (or aren't) redirects.
+    PreferNonRedirect,
+    /// Stop with an error.
+    Fail,
+}
+
+impl DuplicatePolicy {
+    /// Tell whether the `new` page should replace the `kept` one.
+    fn replaces(self, kept: &Page, new: &Page) -> bool {
+        match self {
+            DuplicatePolicy::KeepFirst | DuplicatePolicy::Fail => false,
+            DuplicatePolicy::KeepLowestId => new.pageid < kept.pageid,
+            DuplicatePolicy::PreferNonRedirect => kept.is_redirect && !new.is_redirect,
+        }
     }
 }
 
+impl FromStr for DuplicatePolicy {
+    type Err = String;
 
-/// Match the given `line` in order to extract the triple.
-/// Return the page id (subject), either *namespace* or *title* (predicate)
-/// and the value (object).
-///
-/// Note that no validation is performed on the page id and the value.
-fn match_triple(line: &str) -> Option<[String; 3]> {
-    lazy_static! {
-            static ref RE: Regex = Regex::new(r#"^<(\d+)> <(namespace|title)> "(.*)" ."#).unwrap();
-    }
-    if let Some(caps) = RE.captures(line) {
-        Some([
-            caps.get(1).unwrap().as_str().to_string(),
-            caps.get(2).unwrap().as_str().to_string(),
-            caps.get(3).unwrap().as_str().to_string()
-        ])
-    } else {
-        None
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep-first" => Ok(DuplicatePolicy::KeepFirst),
+            "keep-lowest-id" => Ok(DuplicatePolicy::KeepLowestId),
+            "prefer-non-redirect" => Ok(DuplicatePolicy::PreferNonRedirect),
+            "fail" => Ok(DuplicatePolicy::Fail),
+            _ => Err(format!(
+                "unknown policy {}, expected one of keep-first, keep-lowest-id, \
+                 prefer-non-redirect, fail",
+                s
+            ))
+        }
     }
 }
 
-/// Parse the RDF triples and extract all pages from the `reader`.
-/// Return them as a hashmap with the titles and namespace as keys and the
-/// Pages as the values.
-pub fn pages_from_rdf(reader: impl BufRead) -> Result<HashMap<(String, u32), Page>, Box<dyn Error>> {
-    let mut pages = HashMap::new();
-    let mut triples: Vec<String> = vec![];
+/// Parse the RDF triples, written according to `rdf`, and extract all
+/// pages from the `reader`. Return them as a hashmap with the titles and
+/// namespaces as keys and the pages as values.
+///
+/// The triples are grouped by subject, so they can come in any order. The
+/// predicates other than *namespace*, *title* and *redirect* are ignored.
+/// The triples appearing several times are only reported, but a page with
+/// two different values for the same predicate is an error.
+///
+/// The pages sharing the same title and namespace are reported, and the
+/// one to keep is chosen according to `policy`.
+pub fn pages_from_rdf(
+    reader: impl BufRead,
+    policy: DuplicatePolicy,
+    rdf: &RdfFormat
+) -> Result<HashMap<(String, u32), Page>, Box<dyn Error>> {
+    let mut builders: Vec<PageBuilder> = vec![];
+    let mut indices: FnvHashMap<u64, usize> = FnvHashMap::default();
+    let mut duplicates = 0;
 
-    for line in reader.lines() {
+    for (n, line) in reader.lines().enumerate() {
         let l = line?;
-        if l.is_empty() || l.starts_with("#") {
+        let l = l.trim();
+        if l.is_empty() || l.starts_with('#') {
             continue;
         }
 
-        if triples.len() == 2 {
-            let page = Page::from_rdf(triples)?;
-            pages.insert((page.title.clone(), page.namespace), page.clone());
-            triples = vec![l];
-        } else {
-            triples.push(l);
+        let statement = Statement::parse(l)
+            .map_err(|e| PageError::RDF { triples: format!("line {}: {}", n + 1, e) })?;
+        let pageid = rdf.parse_node(&statement.subject)
+            .ok_or_else(|| PageError::RDF { triples: format!("line {}: {}", n + 1, l) })?;
+        let predicate = match rdf.parse_predicate(&statement.predicate) {
+            Some(predicate) => predicate,
+            None => continue
+        };
+        let value = match statement.object {
+            Term::Literal { value, .. } => value,
+            _ => continue
+        };
+
+        let index = *indices.entry(pageid).or_insert_with(|| {
+            builders.push(PageBuilder {
+                pageid, namespace: None, title: None, is_redirect: None
+            });
+            builders.len() - 1
+        });
+        let builder = &mut builders[index];
+
+        let duplicate = match predicate {
+            "namespace" => {
+                let namespace = value.parse::<u32>().or(
+                    Err(PageError::RDF { triples: format!("line {}: {}", n + 1, l) })
+                )?;
+                PageBuilder::set(pageid, predicate, &mut builder.namespace, namespace)?
+            },
+            "title" => PageBuilder::set(pageid, predicate, &mut builder.title, value)?,
+            "redirect" => {
+                let is_redirect = match value.as_str() {
+                    "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => return Err(Box::new(
+                        PageError::RDF { triples: format!("line {}: {}", n + 1, l) }
+                    ))
+                };
+                PageBuilder::set(pageid, predicate, &mut builder.is_redirect, is_redirect)?
+            },
+            _ => false
+        };
+        if duplicate {
+            duplicates += 1;
         }
     }
 
+    if duplicates > 0 {
+        eprintln!("WARNING: {} duplicated triples ignored.", duplicates);
+    }
+
+    let mut pages = HashMap::with_capacity(builders.len());
+    let mut collisions = 0;
+    for builder in builders {
+        let page = builder.build()?;
+        match pages.entry((page.title.clone(), page.namespace)) {
+            Entry::Vacant(entry) => { entry.insert(page); },
+            Entry::Occupied(mut entry) => {
+                if let DuplicatePolicy::Fail = policy {
+                    return Err(Box::new(PageError::Duplicate {
+                        first: entry.get().to_string(),
+                        second: page.to_string()
+                    }));
+                }
+                let dropped = if policy.replaces(entry.get(), &page) {
+                    entry.insert(page)
+                } else {
+                    page
+                };
+                if collisions < COLLISION_EXAMPLES {
+                    eprintln!("WARNING: title collision, keeping {} and dropping {}.",
+                              entry.get(), dropped);
+                }
+                collisions += 1;
+            }
+        }
+    }
+    if collisions > COLLISION_EXAMPLES {
+        eprintln!("WARNING: {} more title collisions not shown.", collisions - COLLISION_EXAMPLES);
+    }
+    if collisions > 0 {
+        eprintln!("WARNING: {} pages dropped because of a title collision.", collisions);
+    }
+
     Ok(pages)
 }
 
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum PageError {
     SQL{values: String},
     RDF{triples: String},
+    Conflict{pageid: u64, predicate: String, first: String, second: String},
+    Incomplete{pageid: u64},
+    Duplicate{first: String, second: String},
 }
 
 impl fmt::Display for PageError {
@@ -170,12 +359,74 @@ impl fmt::Display for PageError {
         match self {
             PageError::SQL {values} => write!(f, "values: {}", values),
             PageError::RDF {triples} => write!(f, "triples: {}", triples),
+            PageError::Conflict {pageid, predicate, first, second} => write!(
+                f, "page {} has two different {}: \"{}\" and \"{}\"",
+                pageid, predicate, first, second
+            ),
+            PageError::Incomplete {pageid} =>
+                write!(f, "page {} lacks its namespace or its title", pageid),
+            PageError::Duplicate {first, second} =>
+                write!(f, "pages {} and {} share the same title", first, second),
         }
     }
 }
 
 impl Error for PageError {
-    fn cause(&self) -> Option<&'static(dyn Error)> {
+    fn cause(&self) -> Option<&'static dyn Error> {
         None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdf::Flavor;
+
+    fn dgraph() -> RdfFormat {
+        RdfFormat::new(Flavor::Dgraph, None, None, None, None).unwrap()
+    }
+
+    #[test]
+    fn read_grouped_by_subject() {
+        let triples = r#"
+# A comment, then a blank line
+
+<3> <namespace> "0" .
+<7> <title> "Algèbre linéaire" .
+<3> <title> "Antoine Meillet" . # a trailing comment
+<7> <namespace> "0" .
+<7> <redirect> "true" .
+<3> <linksto> <7> .
+"#;
+        let pages = pages_from_rdf(triples.as_bytes(), DuplicatePolicy::Fail, &dgraph()).unwrap();
+        assert_eq!(pages.len(), 2);
+        let page = &pages[&("Antoine Meillet".to_string(), 0)];
+        assert_eq!((page.pageid, page.is_redirect), (3, false));
+        let page = &pages[&("Algèbre linéaire".to_string(), 0)];
+        assert_eq!((page.pageid, page.is_redirect), (7, true));
+    }
+
+    #[test]
+    fn read_errors() {
+        for triples in &[
+            "<3> <namespace> \"0\" .\n",
+            "<3> <namespace> \"zero\" .\n<3> <title> \"A\" .\n",
+            "<3> <namespace> \"0\" .\n<3> <namespace> \"1\" .\n<3> <title> \"A\" .\n",
+            "<3> <title> \"A\" <g> <h> .\n",
+        ] {
+            assert!(pages_from_rdf(triples.as_bytes(), DuplicatePolicy::Fail, &dgraph()).is_err());
+        }
+    }
+
+    #[test]
+    fn title_collisions() {
+        let triples = "<9> <namespace> \"0\" .\n<9> <title> \"A\" .\n<9> <redirect> \"true\" .\n\
+                       <4> <namespace> \"0\" .\n<4> <title> \"A\" .\n";
+        let read = |policy| pages_from_rdf(triples.as_bytes(), policy, &dgraph());
+        let key = ("A".to_string(), 0);
+        assert_eq!(read(DuplicatePolicy::KeepFirst).unwrap()[&key].pageid, 9);
+        assert_eq!(read(DuplicatePolicy::KeepLowestId).unwrap()[&key].pageid, 4);
+        assert_eq!(read(DuplicatePolicy::PreferNonRedirect).unwrap()[&key].pageid, 4);
+        assert!(read(DuplicatePolicy::Fail).is_err());
+    }
+}
@@ -0,0 +1,667 @@
+//! Writing and reading of N-Triples statements.
+//!
+//! The `RdfFormat` type tells how the terms are made: either the way dgraph
+//! expects them (relative IRIs and plain literals), or strictly following
+//! the [N-Triples specification][0] (absolute IRIs and typed literals). It
+//! also tells whether the statements are triples, or quads with a graph
+//! label.
+//!
+//! The `Statement` type is written as one line of a N-Triples (or N-Quads)
+//! file, and can be parsed back from it. The parser follows the
+//! [N-Triples grammar][1] closely enough to read back what this tool
+//! writes, and what most RDF tools produce, but doesn't validate the IRIs
+//! nor the language tags.
+//!
+//! [0]: https://www.w3.org/TR/n-triples/
+//! [1]: https://www.w3.org/TR/n-triples/#n-triples-grammar
+
+
+use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
+
+use crate::dgraph::{self, Ids};
+
+
+/// The IRI of the XML Schema datatypes.
+pub const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// The default IRI of the predicates, in strict flavor.
+pub const DEFAULT_VOCAB_IRI: &str = "https://github.com/Picani/wikipedia-dump-converter/vocab#";
+
+
+/// The flavor of the RDF output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flavor {
+    /// Relative IRIs and plain literals, as accepted by dgraph.
+    Dgraph,
+    /// Absolute IRIs and typed literals, as accepted by any RDF tool.
+    Strict,
+}
+
+impl FromStr for Flavor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dgraph" => Ok(Flavor::Dgraph),
+            "strict" => Ok(Flavor::Strict),
+            _ => Err(format!("unknown RDF flavor {}, expected dgraph or strict", s))
+        }
+    }
+}
+
+
+/// Tell how to write (and read back) the RDF terms.
+#[derive(Clone, Debug)]
+pub struct RdfFormat {
+    flavor: Flavor,
+    /// The IRI the page IDs are appended to, in strict flavor.
+    base_iri: String,
+    /// The IRI the predicate names are appended to, in strict flavor.
+    vocab_iri: String,
+    /// The language tag of the titles, in strict flavor.
+    lang: Option<String>,
+    /// The graph label of the statements, when writing N-Quads.
+    graph: Option<String>,
+    /// Whether the type of the nodes is written, for dgraph.
+    types: bool,
+    /// How the nodes are identified, in dgraph flavor.
+    ids: Ids,
+    /// The prefix of the blank node labels, in dgraph flavor.
+    id_prefix: String,
+}
+
+impl RdfFormat {
+    /// Make a new format. The `base_iri` is mandatory in strict flavor, and
+    /// can't be given in dgraph flavor, like `vocab_iri` and `lang`. If a
+    /// `graph` IRI is given, the statements are written as N-Quads in this
+    /// graph. This IRI must be absolute in strict flavor.
+    pub fn new(
+        flavor: Flavor,
+        base_iri: Option<String>,
+        vocab_iri: Option<String>,
+        lang: Option<String>,
+        graph: Option<String>
+    ) -> Result<RdfFormat, RdfError> {
+        if flavor == Flavor::Dgraph {
+            let options = [
+                ("base IRI", base_iri.is_some()),
+                ("vocabulary IRI", vocab_iri.is_some()),
+                ("language tag", lang.is_some()),
+            ];
+            if let Some((option, _)) = options.iter().find(|(_, given)| *given) {
+                return Err(RdfError::StrictOnly { option });
+            }
+        }
+        if let (Flavor::Strict, Some(graph)) = (flavor, &graph) {
+            if !is_absolute(graph) {
+                return Err(RdfError::RelativeGraph { graph: graph.clone() });
+            }
+        }
+        let base_iri = match (flavor, base_iri) {
+            (Flavor::Strict, None) => return Err(RdfError::MissingBaseIri),
+            (_, base_iri) => base_iri.unwrap_or_default()
+        };
+        Ok(RdfFormat {
+            flavor,
+            base_iri,
+            vocab_iri: vocab_iri.unwrap_or_else(|| DEFAULT_VOCAB_IRI.to_string()),
+            lang,
+            graph,
+            types: false,
+            ids: Ids::Uid,
+            id_prefix: String::new(),
+        })
+    }
+
+    /// Identify the nodes according to `ids`, prefixing the blank node
+    /// labels with `prefix`. Only available in dgraph flavor.
+    pub fn with_ids(mut self, ids: Ids, prefix: &str) -> Result<RdfFormat, RdfError> {
+        if ids != Ids::Uid && self.flavor != Flavor::Dgraph {
+            return Err(RdfError::Ids { reason: "they need the dgraph flavor".to_string() });
+        }
+        if !dgraph::is_valid_prefix(prefix) {
+            return Err(RdfError::Ids { reason: format!("invalid prefix {}", prefix) });
+        }
+        self.ids = ids;
+        self.id_prefix = prefix.to_string();
+        Ok(self)
+    }
+
+    /// Return how the nodes are identified.
+    pub fn ids(&self) -> Ids {
+        self.ids
+    }
+
+    /// Also write the type of the nodes, with the `dgraph.type` predicate.
+    pub fn with_types(mut self) -> RdfFormat {
+        self.types = true;
+        self
+    }
+
+    /// Tell whether the type of the nodes is written.
+    pub fn types(&self) -> bool {
+        self.types
+    }
+
+    /// Return the flavor.
+    pub fn flavor(&self) -> Flavor {
+        self.flavor
+    }
+
+    /// Make the node of the page `pageid`.
+    pub fn node(&self, pageid: u64) -> Term {
+        match self.ids {
+            Ids::Uid => Term::Iri(format!("{}{}", self.base_iri, pageid)),
+            Ids::Blank | Ids::Xid => Term::Blank(self.label(pageid)),
+        }
+    }
+
+    /// Make the blank node label, or external ID, of the page `pageid`.
+    pub fn label(&self, pageid: u64) -> String {
+        format!("{}{}", self.id_prefix, pageid)
+    }
+
+    /// Make the predicate `name`.
+    pub fn predicate(&self, name: &str) -> Term {
+        match self.flavor {
+            Flavor::Dgraph => Term::Iri(name.to_string()),
+            Flavor::Strict => Term::Iri(format!("{}{}", self.vocab_iri, name)),
+        }
+    }
+
+    /// Make the literal `value` with the XML Schema `datatype` in strict
+    /// flavor, or a plain literal in dgraph flavor.
+    fn typed(&self, value: String, datatype: &str) -> Term {
+        let datatype = match self.flavor {
+            Flavor::Dgraph => None,
+            Flavor::Strict => Some(format!("{}{}", XSD, datatype)),
+        };
+        Term::Literal { value, datatype, lang: None }
+    }
+
+    /// Make the integer literal `value`.
+    pub fn integer(&self, value: impl fmt::Display) -> Term {
+        self.typed(value.to_string(), "integer")
+    }
+
+    /// Make the boolean literal `value`.
+    pub fn boolean(&self, value: bool) -> Term {
+        self.typed(value.to_string(), "boolean")
+    }
+
+    /// Make the plain string literal `value`.
+    pub fn string(&self, value: &str) -> Term {
+        Term::Literal { value: value.to_string(), datatype: None, lang: None }
+    }
+
+    /// Make the title literal `value`.
+    pub fn title(&self, value: &str) -> Term {
+        let lang = match self.flavor {
+            Flavor::Dgraph => None,
+            Flavor::Strict => self.lang.clone(),
+        };
+        Term::Literal { value: value.to_string(), datatype: None, lang }
+    }
+
+    /// Make the statement made of the `subject`, `predicate` and `object`
+    /// terms, in the graph if any.
+    pub fn statement(&self, subject: Term, predicate: Term, object: Term) -> Statement {
+        let graph = self.graph.as_ref().map(|graph| Term::Iri(graph.clone()));
+        Statement { subject, predicate, object, graph }
+    }
+
+    /// Return the prefixes of the IRIs, with their names, in strict
+    /// flavor. There is none in dgraph flavor.
+    pub fn prefixes(&self) -> Vec<(&str, &str)> {
+        match self.flavor {
+            Flavor::Dgraph => vec![],
+            Flavor::Strict => vec![
+                ("page", self.base_iri.as_str()),
+                ("vocab", self.vocab_iri.as_str()),
+                ("xsd", XSD),
+            ],
+        }
+    }
+
+    /// Tell whether the statements are written in a graph.
+    pub fn has_graph(&self) -> bool {
+        self.graph.is_some()
+    }
+
+    /// Get back the page ID from a node made by `node`.
+    pub fn parse_node(&self, term: &Term) -> Option<u64> {
+        match (self.ids, term) {
+            (Ids::Uid, Term::Iri(iri)) => iri.strip_prefix(self.base_iri.as_str())?.parse().ok(),
+            (Ids::Blank, Term::Blank(label)) | (Ids::Xid, Term::Blank(label)) =>
+                label.strip_prefix(self.id_prefix.as_str())?.parse().ok(),
+            _ => None
+        }
+    }
+
+    /// Get back the predicate name from a predicate made by `predicate`.
+    pub fn parse_predicate<'a>(&self, term: &'a Term) -> Option<&'a str> {
+        match (self.flavor, term) {
+            (Flavor::Dgraph, Term::Iri(iri)) => Some(iri),
+            (Flavor::Strict, Term::Iri(iri)) => iri.strip_prefix(self.vocab_iri.as_str()),
+            _ => None
+        }
+    }
+}
+
+
+/// A term of a RDF statement.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Term {
+    Iri(String),
+    Blank(String),
+    Literal{value: String, datatype: Option<String>, lang: Option<String>},
+}
+
+/// A RDF statement. The graph label is only present in N-Quads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub subject: Term,
+    pub predicate: Term,
+    pub object: Term,
+    pub graph: Option<Term>,
+}
+
+/// Write the term following the N-Triples grammar.
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Iri(iri) => write!(f, "<{}>", iri),
+            Term::Blank(label) => write!(f, "_:{}", label),
+            Term::Literal {value, datatype: Some(datatype), ..} =>
+                write!(f, "\"{}\"^^<{}>", escape(value), datatype),
+            Term::Literal {value, lang: Some(lang), ..} =>
+                write!(f, "\"{}\"@{}", escape(value), lang),
+            Term::Literal {value, ..} => write!(f, "\"{}\"", escape(value)),
+        }
+    }
+}
+
+/// Write the statement as a N-Triples (or N-Quads) line, without the
+/// newline character.
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} ", self.subject, self.predicate, self.object)?;
+        if let Some(graph) = &self.graph {
+            write!(f, "{} ", graph)?;
+        }
+        write!(f, ".")
+    }
+}
+
+impl Statement {
+    /// Parse a N-Triples or N-Quads `line`.
+    ///
+    /// The escape sequences of the literals are decoded, but not the ones of
+    /// the IRIs.
+    pub fn parse(line: &str) -> Result<Statement, RdfError> {
+        let err = || RdfError::Syntax { line: line.to_string() };
+        let mut cursor = Cursor { data: line, pos: 0 };
+
+        let subject = cursor.term().ok_or_else(err)?;
+        let predicate = cursor.term().ok_or_else(err)?;
+        let object = cursor.term().ok_or_else(err)?;
+        let graph = if cursor.peek() == Some('.') { None } else {
+            Some(cursor.term().ok_or_else(err)?)
+        };
+
+        if cursor.peek() != Some('.') {
+            return Err(err());
+        }
+        cursor.pos += 1;
+        cursor.skip_spaces();
+        if !cursor.rest().is_empty() && !cursor.rest().starts_with('#') {
+            return Err(err());
+        }
+
+        match (&subject, &predicate, &object, &graph) {
+            (Term::Literal{..}, _, _, _) | (_, Term::Literal{..}, _, _) |
+            (_, Term::Blank(_), _, _) | (_, _, _, Some(Term::Literal{..})) => Err(err()),
+            _ => Ok(Statement { subject, predicate, object, graph })
+        }
+    }
+}
+
+
+/// Tell whether the `iri` is absolute, that is starts with a scheme
+/// (`https:`, `urn:`, _etc_).
+fn is_absolute(iri: &str) -> bool {
+    match iri.split_once(':') {
+        Some((scheme, _)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        },
+        None => false
+    }
+}
+
+
+/// Escape the string `value` following the N-Triples grammar, so it can be
+/// written between double quotes.
+pub fn escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04X}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Decode the escape sequences of the string `value`, following the
+/// N-Triples grammar. Return `None` if an escape sequence is invalid.
+pub fn unescape(value: &str) -> Option<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            't' => result.push('\t'),
+            'b' => result.push('\u{8}'),
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            'f' => result.push('\u{c}'),
+            c @ '"' | c @ '\'' | c @ '\\' => result.push(c),
+            c @ 'u' | c @ 'U' => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let hex: String = chars.by_ref().take(len).collect();
+                if hex.len() != len {
+                    return None;
+                }
+                let code = u32::from_str_radix(&hex, 16).ok()?;
+                result.push(std::char::from_u32(code)?);
+            },
+            _ => return None
+        }
+    }
+    Some(result)
+}
+
+
+/// Helper to walk through a line while parsing it.
+struct Cursor<'a> {
+    data: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn rest(&self) -> &'a str {
+        &self.data[self.pos..]
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+
+    /// Return the next non-space character without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        self.skip_spaces();
+        self.rest().chars().next()
+    }
+
+    /// Consume the characters until `end` (excluded) is found outside of an
+    /// escape sequence. Return them, and skip `end`.
+    fn until(&mut self, end: char) -> Option<&'a str> {
+        let rest = self.rest();
+        let mut escaped = false;
+        for (i, c) in rest.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == end {
+                self.pos += i + c.len_utf8();
+                return Some(&rest[..i]);
+            }
+        }
+        None
+    }
+
+    /// Consume the characters as long as `pred` holds.
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// Parse the next term.
+    fn term(&mut self) -> Option<Term> {
+        match self.peek()? {
+            '<' => {
+                self.pos += 1;
+                Some(Term::Iri(self.until('>')?.to_string()))
+            },
+            '_' => {
+                if !self.rest().starts_with("_:") {
+                    return None;
+                }
+                self.pos += 2;
+                let label = self.take_while(|c| !c.is_whitespace());
+                // A label directly followed by the final dot is allowed.
+                let label = label.strip_suffix('.').map_or(label, |l| {
+                    self.pos -= 1;
+                    l
+                });
+                if label.is_empty() { None } else { Some(Term::Blank(label.to_string())) }
+            },
+            '"' => {
+                self.pos += 1;
+                let value = unescape(self.until('"')?)?;
+                let mut datatype = None;
+                let mut lang = None;
+                if self.rest().starts_with("^^<") {
+                    self.pos += 3;
+                    datatype = Some(self.until('>')?.to_string());
+                } else if self.rest().starts_with('@') {
+                    self.pos += 1;
+                    let tag = self.take_while(|c| c.is_ascii_alphanumeric() || c == '-');
+                    if tag.is_empty() {
+                        return None;
+                    }
+                    lang = Some(tag.to_string());
+                }
+                Some(Term::Literal { value, datatype, lang })
+            },
+            _ => None
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub enum RdfError {
+    Syntax{line: String},
+    MissingBaseIri,
+    StrictOnly{option: &'static str},
+    RelativeGraph{graph: String},
+    Ids{reason: String},
+}
+
+impl fmt::Display for RdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RdfError::Syntax {line} => write!(f, "invalid statement: {}", line),
+            RdfError::MissingBaseIri => write!(f, "the strict flavor needs a base IRI"),
+            RdfError::StrictOnly {option} => write!(f, "the {} needs the strict flavor", option),
+            RdfError::RelativeGraph {graph} =>
+                write!(f, "the graph {} must be an absolute IRI in strict flavor", graph),
+            RdfError::Ids {reason} => write!(f, "invalid dgraph IDs: {}", reason),
+        }
+    }
+}
+
+impl Error for RdfError {
+    fn cause(&self) -> Option<&'static dyn Error> {
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iri(iri: &str) -> Term {
+        Term::Iri(iri.to_string())
+    }
+
+    fn literal(value: &str, datatype: Option<&str>, lang: Option<&str>) -> Term {
+        Term::Literal {
+            value: value.to_string(),
+            datatype: datatype.map(str::to_string),
+            lang: lang.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        for value in &[
+            "plain", "Le \"Quote\"", "Back\\slash", "two\nlines\r\n", "tab\there",
+            "nul\u{0}bell\u{7}", "Algèbre 😀", "", "\\\"",
+        ] {
+            assert_eq!(unescape(&escape(value)).as_deref(), Some(*value));
+        }
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(escape("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+        assert_eq!(escape("\u{1}"), r"\u0001");
+        assert_eq!(unescape(r"\u00E9\U0001F600").as_deref(), Some("é😀"));
+        assert_eq!(unescape(r"\b\f\'").as_deref(), Some("\u{8}\u{c}'"));
+    }
+
+    #[test]
+    fn unescape_invalid() {
+        assert_eq!(unescape(r"\"), None);
+        assert_eq!(unescape(r"\q"), None);
+        assert_eq!(unescape(r"\u12"), None);
+        assert_eq!(unescape(r"\uZZZZ"), None);
+        assert_eq!(unescape(r"\UFFFFFFFF"), None);
+        assert_eq!(unescape(r"\uD800"), None);
+    }
+
+    #[test]
+    fn parse_triple() {
+        let statement = Statement::parse(r#"<3> <title> "Antoine Meillet" ."#).unwrap();
+        assert_eq!(statement, Statement {
+            subject: iri("3"),
+            predicate: iri("title"),
+            object: literal("Antoine Meillet", None, None),
+            graph: None,
+        });
+    }
+
+    #[test]
+    fn parse_literals() {
+        let statement = Statement::parse(
+            r#"<http://a/3> <http://v/ns> "0"^^<http://www.w3.org/2001/XMLSchema#integer> ."#
+        ).unwrap();
+        assert_eq!(
+            statement.object,
+            literal("0", Some("http://www.w3.org/2001/XMLSchema#integer"), None)
+        );
+        let statement = Statement::parse(r#"<3> <title> "Algèbre"@fr-CA ."#).unwrap();
+        assert_eq!(statement.object, literal("Algèbre", None, Some("fr-CA")));
+        let statement = Statement::parse(r#"<3> <title> "a \"b\" é\n" ."#).unwrap();
+        assert_eq!(statement.object, literal("a \"b\" é\n", None, None));
+    }
+
+    #[test]
+    fn parse_blank_nodes() {
+        let statement = Statement::parse("_:p3 <linksto> _:p7.").unwrap();
+        assert_eq!(statement.subject, Term::Blank("p3".to_string()));
+        assert_eq!(statement.object, Term::Blank("p7".to_string()));
+        assert!(Statement::parse("_: <linksto> <7> .").is_err());
+        assert!(Statement::parse("<3> _:p <7> .").is_err());
+    }
+
+    #[test]
+    fn parse_quads() {
+        let statement = Statement::parse("<3> <linksto> <7> <frwiki> .").unwrap();
+        assert_eq!(statement.graph, Some(iri("frwiki")));
+        let statement = Statement::parse("<3> <linksto> <7> _:g .").unwrap();
+        assert_eq!(statement.graph, Some(Term::Blank("g".to_string())));
+        assert!(Statement::parse(r#"<3> <linksto> <7> "graph" ."#).is_err());
+    }
+
+    #[test]
+    fn parse_comments() {
+        let statement = Statement::parse("<3> <linksto> <7> . # a comment").unwrap();
+        assert_eq!(statement.object, iri("7"));
+        assert!(Statement::parse("# a comment").is_err());
+        assert!(Statement::parse("").is_err());
+    }
+
+    #[test]
+    fn parse_malformed() {
+        for line in &[
+            "<3> <linksto>",
+            "<3> <linksto> <7>",
+            "<3> <linksto> <7 .",
+            "<3> <linksto> <7> . junk",
+            r#"<3> <title> "unterminated ."#,
+            r#"<3> <title> "bad \q escape" ."#,
+            r#"<3> <title> "no tag"@ ."#,
+            r#"<3> <title> "x"^^<unterminated ."#,
+            r#""literal" <title> <3> ."#,
+            r#"<3> "literal" <7> ."#,
+            "<3> <linksto> <7> <g> <h> .",
+            "3 <linksto> <7> .",
+            r#"<3> <title> "trailing backslash\"#,
+        ] {
+            assert!(Statement::parse(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn strict_only_options() {
+        let new = |base_iri: Option<&str>, lang: Option<&str>| RdfFormat::new(
+            Flavor::Dgraph, base_iri.map(str::to_string), None, lang.map(str::to_string), None
+        );
+        assert!(new(None, None).is_ok());
+        assert!(matches!(new(Some("http://a/"), None), Err(RdfError::StrictOnly { .. })));
+        assert!(matches!(new(None, Some("fr")), Err(RdfError::StrictOnly { .. })));
+    }
+
+    #[test]
+    fn strict_graph() {
+        let new = |graph: &str| RdfFormat::new(
+            Flavor::Strict, Some("http://a/".to_string()), None, None, Some(graph.to_string())
+        );
+        assert!(new("https://fr.wikipedia.org/frwiki-20200901").is_ok());
+        assert!(new("urn:x-wiki:frwiki-20200901").is_ok());
+        assert!(matches!(new("frwiki-20200901"), Err(RdfError::RelativeGraph { .. })));
+        assert!(matches!(new(":frwiki"), Err(RdfError::RelativeGraph { .. })));
+        assert!(matches!(new("1a:frwiki"), Err(RdfError::RelativeGraph { .. })));
+    }
+
+    #[test]
+    fn write_then_parse() {
+        let rdf = RdfFormat::new(
+            Flavor::Strict, Some("http://fr.wikipedia.org/?curid=".to_string()), None,
+            Some("fr".to_string()), Some("http://example.org/graph".to_string())
+        ).unwrap();
+        let statement = rdf.statement(rdf.node(3), rdf.predicate("title"), rdf.title("a\"b\nc"));
+        assert_eq!(Statement::parse(&statement.to_string()).unwrap(), statement);
+        assert_eq!(rdf.parse_node(&statement.subject), Some(3));
+        assert_eq!(rdf.parse_predicate(&statement.predicate), Some("title"));
+    }
+}
@@ -115,4 +115,68 @@ impl Iterator for InsertParser {
             self.curr_pos += 1;
         }
     }
-}
\ No newline at end of file
+}
+
+/// Decode the escape sequences of a string field, as written by
+/// `mysqldump`. The unknown sequences are kept as they are.
+pub fn unescape(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => result.push('\0'),
+            Some('b') => result.push('\u{8}'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('Z') => result.push('\u{1a}'),
+            Some(c @ '\\') | Some(c @ '\'') | Some(c @ '"') => result.push(c),
+            Some(c) => {
+                result.push('\\');
+                result.push(c);
+            },
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescape_mysqldump() {
+        assert_eq!(unescape("plain_title"), "plain_title");
+        assert_eq!(unescape(r"nul\0"), "nul\0");
+        assert_eq!(unescape(r"back\bspace"), "back\u{8}space");
+        assert_eq!(unescape(r"new\nline"), "new\nline");
+        assert_eq!(unescape(r"carriage\rreturn"), "carriage\rreturn");
+        assert_eq!(unescape(r"tab\there"), "tab\there");
+        assert_eq!(unescape(r"ctrl\Z"), "ctrl\u{1a}");
+        assert_eq!(unescape(r"back\\slash"), r"back\slash");
+        assert_eq!(unescape(r"l\'a"), "l'a");
+        assert_eq!(unescape(r#"\"quote\""#), "\"quote\"");
+    }
+
+    #[test]
+    fn unescape_unknown() {
+        assert_eq!(unescape(r"percent\%"), r"percent\%");
+        assert_eq!(unescape(r"trailing\"), r"trailing\");
+    }
+
+    #[test]
+    fn parse_values() {
+        let line = r"INSERT INTO `page` VALUES (3,0,'A\'s_(b)',1),(4,14,'x,y',0);".to_string();
+        let values: Vec<Vec<String>> = InsertParser::from_line(line).collect();
+        assert_eq!(values, vec![
+            vec!["3", "0", r"A\'s_(b)", "1"],
+            vec!["4", "14", "x,y", "0"],
+        ]);
+    }
+}
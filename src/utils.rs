@@ -1,47 +1,129 @@
 //! Utility functions
 
-use std::io::{Write, BufWriter};
 use std::sync::mpsc::Receiver;
 
+use fnv::FnvHashSet;
 use indicatif::{ProgressBar, ProgressStyle, ProgressDrawTarget};
 
-/// Receive triples through `rx` and write them to `out`.
-/// Take care of the buffering, and print a progress bar.
-pub fn write_triples<W: Write>(
-    out: W,
-    rx: Receiver<String>
+use crate::output::{Record, RecordWriter};
+use crate::sql;
+
+/// Receive records through `rx` and write them with `writer`.
+/// Print a progress bar.
+pub fn write_records(
+    mut writer: Box<dyn RecordWriter>,
+    rx: Receiver<Record>
 ) -> std::io::Result<()> {
     let pb = ProgressBar::new(0)
         .with_style(ProgressStyle::default_bar()
-            .template("Writing RDF triples... Elapsed time: {elapsed_precise} - Written: {pos} triples [{per_sec}]"));
+            .template("Writing records... Elapsed time: {elapsed_precise} - Written: {pos} records [{per_sec}]"));
     pb.set_draw_target(ProgressDrawTarget::stdout());
 
-    let mut stream = BufWriter::new(out);
-    while let Ok(triple) = rx.recv() {
-        stream.write(triple.as_bytes())?;
-        stream.write(b"\n")?;
+    while let Ok(record) = rx.recv() {
+        writer.write(&record)?;
         pb.inc(1);
     }
-    stream.flush()?;
+    writer.finish()?;
     pb.finish();
 
     Ok(())
 }
 
-/// Clean a page title up.
-pub fn clean_title(title: &String) -> String {
-    let mut result = String::new();
-    for c in title.chars() {
-        if c == '_' {
-            result.push(' ');
-        } else if c == '\\' {
-            continue;
-        } else if c == '"' {
-            result.push('\\');
-            result.push('"');
+/// Clean a page title up: decode its SQL escape sequences and replace the
+/// underscores by spaces.
+///
+/// Note that the title is not escaped for any output format. This is done
+/// when writing it.
+pub fn clean_title(title: &str) -> String {
+    sql::unescape(title).replace('_', " ")
+}
+
+/// Quote the CSV `field`, doubling its quotes.
+pub fn csv_quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// Escape the `value` for XML text or attributes. The characters XML
+/// doesn't allow, the control characters other than the tab, the line feed
+/// and the carriage return, are replaced by U+FFFD.
+pub fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\u{0}'..='\u{8}' | '\u{b}' | '\u{c}' | '\u{e}'..='\u{1f}'
+                | '\u{fffe}' | '\u{ffff}' => escaped.push(char::REPLACEMENT_CHARACTER),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Return a path in the temporary directory, unique to the test `name`
+/// and to the process running the tests.
+#[cfg(test)]
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("wdc-{}-{}", std::process::id(), name))
+}
+
+/// A set of namespaces to keep. A filter made from no namespace at all
+/// keeps all of them.
+#[derive(Clone, Debug, Default)]
+pub struct NamespaceFilter {
+    namespaces: Option<FnvHashSet<u32>>
+}
+
+impl NamespaceFilter {
+    /// Make a filter keeping the given `namespaces`, or all of them if
+    /// there is none.
+    pub fn new(namespaces: &[u32]) -> NamespaceFilter {
+        if namespaces.is_empty() {
+            NamespaceFilter { namespaces: None }
         } else {
-            result.push(c);
+            NamespaceFilter { namespaces: Some(namespaces.iter().copied().collect()) }
         }
     }
-    result
-}
\ No newline at end of file
+
+    /// Make a filter keeping only the namespaces kept by both `self` and
+    /// `other`.
+    pub fn and(&self, other: &NamespaceFilter) -> NamespaceFilter {
+        let namespaces = match (&self.namespaces, &other.namespaces) {
+            (None, None) => None,
+            (Some(n), None) | (None, Some(n)) => Some(n.clone()),
+            (Some(n), Some(m)) => Some(n.intersection(m).copied().collect()),
+        };
+        NamespaceFilter { namespaces }
+    }
+
+    /// Tell whether the `namespace` is kept by the filter.
+    pub fn accepts(&self, namespace: u32) -> bool {
+        match &self.namespaces {
+            None => true,
+            Some(n) => n.contains(&namespace)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xml_escape_markup() {
+        assert_eq!(xml_escape(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;");
+    }
+
+    #[test]
+    fn xml_escape_control_chars() {
+        assert_eq!(xml_escape("a\tb\nc\rd"), "a\tb\nc\rd");
+        assert_eq!(xml_escape("a\u{0}b\u{8}c\u{b}d\u{c}e\u{1b}f\u{1f}g\u{ffff}"),
+            "a\u{fffd}b\u{fffd}c\u{fffd}d\u{fffd}e\u{fffd}f\u{fffd}g\u{fffd}");
+        assert_eq!(xml_escape("\u{7f}é\u{fffd}"), "\u{7f}é\u{fffd}");
+    }
+}